@@ -0,0 +1,31 @@
+// [[file:../xtb.note::d25e8c4f][d25e8c4f]]
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::{XtbModel, XtbParameters};
+
+/// Repeated single points on the same system should not pay the setup cost of
+/// parametrization again, which is only paid by a freshly created model.
+fn bench_single_point(c: &mut Criterion) {
+    for method in ["GFN2-xTB", "GFN-FF"] {
+        let mut params = XtbParameters::default();
+        params.method(method);
+
+        let mut gradient = ATOM_COORDS;
+        let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, params.clone()).unwrap();
+        c.bench_function(&format!("{} repeated single point", method), |b| {
+            b.iter(|| xtb.calculate_energy_and_gradient(black_box(&mut gradient)).unwrap())
+        });
+        assert_eq!(xtb.parametrization_loads(), 1);
+
+        c.bench_function(&format!("{} setup and single point", method), |b| {
+            b.iter(|| {
+                let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, params.clone()).unwrap();
+                xtb.calculate_energy_and_gradient(black_box(&mut gradient)).unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_single_point);
+criterion_main!(benches);
+// d25e8c4f ends here
@@ -0,0 +1,366 @@
+// [[file:../xtb.note::9e4b7f20][9e4b7f20]]
+//! Basin-hopping global optimization for clusters and small molecules
+// 9e4b7f20 ends here
+
+// [[file:../xtb.note::c63a0d8e][c63a0d8e]]
+use crate::internal::fragments;
+use crate::md::KB;
+use crate::opt::{Lbfgs, OptConvergence};
+use crate::potential::Potential;
+use crate::rmsd::rmsd;
+use crate::xyz::write_xyz;
+
+use anyhow::*;
+use nalgebra::{Rotation3, Unit, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, StandardNormal};
+use std::path::Path;
+// c63a0d8e ends here
+
+// [[file:../xtb.note::27f5c1b9][27f5c1b9]]
+/// Basin hopping of Wales and Doye (J. Phys. Chem. A 101, 5111 (1997)).
+/// Each step perturbs the current minimum, either by random displacements
+/// of all atoms or by a rigid rotation of a randomly chosen fragment, and
+/// minimizes the result locally. The new minimum is accepted with
+/// Metropolis criterion at a fixed temperature. Distinct minima are kept
+/// in an archive sorted by energy.
+///
+/// Minima are considered the same if they differ in energy less than the
+/// threshold, and either their RMSD or the RMS difference of their sorted
+/// interatomic distances is below the distance threshold. The latter does
+/// not depend on the numbering of identical atoms, so permutational
+/// isomers of clusters are recognized as the same minimum.
+#[derive(Clone, Debug)]
+pub struct BasinHopping {
+    atom_types: Vec<i32>,
+    temperature: f64,
+    max_displacement: f64,
+    max_rotation: f64,
+    rotation_probability: f64,
+    n_steps: usize,
+    max_minima: usize,
+    energy_threshold: f64,
+    rmsd_threshold: f64,
+    optimizer: Lbfgs,
+    seed: Option<u64>,
+}
+
+/// A local minimum found by basin hopping.
+#[derive(Clone, Debug)]
+pub struct Minimum {
+    /// Energy in Hartree
+    pub energy: f64,
+    /// Positions in Bohr
+    pub positions: Vec<f64>,
+    /// Basin-hopping step where the minimum was first found, 0 for the
+    /// minimized starting structure
+    pub step: usize,
+}
+
+/// Progress of basin hopping after each step, passed to user callback.
+#[derive(Debug)]
+pub struct BasinHoppingProgress {
+    /// Current step number, 0 for the minimized starting structure
+    pub step: usize,
+    /// Energy of the new minimum in Hartree, or None if local
+    /// optimization failed or did not converge
+    pub energy: Option<f64>,
+    /// Whether the new minimum was accepted
+    pub accepted: bool,
+    /// Energy of the current minimum in Hartree
+    pub current_energy: f64,
+    /// Lowest energy found so far in Hartree
+    pub lowest_energy: f64,
+}
+
+/// Final report of basin hopping.
+#[derive(Clone, Debug)]
+pub struct BasinHoppingReport {
+    /// Atomic numbers of atoms
+    pub atom_types: Vec<i32>,
+    /// Distinct minima in ascending order of energy
+    pub minima: Vec<Minimum>,
+    /// Number of basin-hopping steps taken
+    pub n_steps: usize,
+    /// Number of accepted steps
+    pub n_accepted: usize,
+}
+
+impl BasinHoppingReport {
+    /// Return the lowest minimum.
+    pub fn lowest(&self) -> &Minimum {
+        &self.minima[0]
+    }
+
+    /// Return the ratio of accepted steps.
+    pub fn acceptance_ratio(&self) -> f64 {
+        if self.n_steps == 0 {
+            0.0
+        } else {
+            self.n_accepted as f64 / self.n_steps as f64
+        }
+    }
+
+    /// Write all minima into `path` in xyz format in ascending order of
+    /// energy. The comment line of each frame has the energy.
+    pub fn write_xyz(&self, path: impl AsRef<Path>) -> Result<()> {
+        let frames = self
+            .minima
+            .iter()
+            .map(|m| (format!("energy= {:.10} step= {}", m.energy, m.step), m.positions.as_slice()));
+        write_xyz(path, &self.atom_types, frames)
+    }
+}
+
+impl BasinHopping {
+    /// Create basin hopping for molecule or cluster with `atom_types` in
+    /// atomic numbers.
+    pub fn new(atom_types: &[i32]) -> Self {
+        Self {
+            atom_types: atom_types.to_vec(),
+            temperature: 300.0,
+            max_displacement: 0.8,
+            max_rotation: 90.0,
+            rotation_probability: 0.5,
+            n_steps: 100,
+            max_minima: 10,
+            energy_threshold: 1e-5,
+            rmsd_threshold: 0.2,
+            optimizer: Lbfgs::default(),
+            seed: None,
+        }
+    }
+
+    /// Set temperature in K for Metropolis acceptance. The default is 300
+    /// K.
+    pub fn temperature(&mut self, t: f64) -> &mut Self {
+        assert!(t >= 0.0, "invalid temperature: {}", t);
+        self.temperature = t;
+        self
+    }
+
+    /// Set largest random displacement of each Cartesian component in Bohr.
+    /// The default is 0.8 Bohr.
+    pub fn max_displacement(&mut self, d: f64) -> &mut Self {
+        assert!(d > 0.0, "invalid displacement: {}", d);
+        self.max_displacement = d;
+        self
+    }
+
+    /// Set largest angle of fragment rotation in degrees. The default is 90
+    /// degrees.
+    pub fn max_rotation(&mut self, angle: f64) -> &mut Self {
+        assert!(angle > 0.0, "invalid rotation angle: {}", angle);
+        self.max_rotation = angle;
+        self
+    }
+
+    /// Set probability of rotating a fragment instead of displacing atoms,
+    /// when the structure has more than one fragment. The default is 0.5.
+    pub fn rotation_probability(&mut self, p: f64) -> &mut Self {
+        assert!((0.0..=1.0).contains(&p), "invalid probability: {}", p);
+        self.rotation_probability = p;
+        self
+    }
+
+    /// Set number of basin-hopping steps. The default is 100.
+    pub fn steps(&mut self, n: usize) -> &mut Self {
+        self.n_steps = n;
+        self
+    }
+
+    /// Keep at most `n` lowest minima in the archive. The default is 10.
+    pub fn max_minima(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of minima: {}", n);
+        self.max_minima = n;
+        self
+    }
+
+    /// Set thresholds of energy difference in Hartree and RMSD in Bohr
+    /// below which two minima are the same. The RMSD threshold also applies
+    /// to sorted interatomic distances. The defaults are 1e-5 Hartree and
+    /// 0.2 Bohr.
+    pub fn duplicate_thresholds(&mut self, energy: f64, rmsd: f64) -> &mut Self {
+        self.energy_threshold = energy;
+        self.rmsd_threshold = rmsd;
+        self
+    }
+
+    /// Set convergence criteria of local optimizations.
+    pub fn convergence(&mut self, convergence: OptConvergence) -> &mut Self {
+        self.optimizer.convergence(convergence);
+        self
+    }
+
+    /// Set maximum number of steps of local optimizations.
+    pub fn max_steps(&mut self, n: usize) -> &mut Self {
+        self.optimizer.max_steps(n);
+        self
+    }
+
+    /// Set seed of random number generator for reproducible runs.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Run basin hopping on `pot` starting from `positions` in Bohr.
+    pub fn run<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<BasinHoppingReport> {
+        self.run_with(pot, positions, |_| true)
+    }
+
+    /// Run basin hopping on `pot` starting from `positions` in Bohr, and
+    /// call `callback` after each step. The run stops early if `callback`
+    /// returns false.
+    pub fn run_with<P, F>(&self, pot: &mut P, positions: &[f64], mut callback: F) -> Result<BasinHoppingReport>
+    where
+        P: Potential + ?Sized,
+        F: FnMut(&BasinHoppingProgress) -> bool,
+    {
+        ensure!(positions.len() == 3 * self.atom_types.len(), "positions do not match atom types");
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let report = self.optimizer.minimize(pot, positions)?;
+        let mut current = Minimum {
+            energy: report.energy,
+            positions: report.positions,
+            step: 0,
+        };
+        let mut minima = vec![];
+        self.archive(&mut minima, &current);
+        let progress = BasinHoppingProgress {
+            step: 0,
+            energy: Some(current.energy),
+            accepted: true,
+            current_energy: current.energy,
+            lowest_energy: current.energy,
+        };
+        let mut n_steps = 0;
+        let mut n_accepted = 0;
+        if callback(&progress) {
+            for step in 1..=self.n_steps {
+                n_steps = step;
+                let x = self.perturb(&current.positions, &mut rng)?;
+                // a failed local optimization is rejected like an unconverged one
+                let report = self.optimizer.minimize(pot, &x).ok().filter(|r| r.converged());
+                let mut progress = BasinHoppingProgress {
+                    step,
+                    energy: None,
+                    accepted: false,
+                    current_energy: current.energy,
+                    lowest_energy: minima[0].energy,
+                };
+                if let Some(report) = report {
+                    let new = Minimum {
+                        energy: report.energy,
+                        positions: report.positions,
+                        step,
+                    };
+                    self.archive(&mut minima, &new);
+                    progress.energy = Some(new.energy);
+                    progress.lowest_energy = minima[0].energy;
+                    if self.metropolis(new.energy - current.energy, &mut rng) {
+                        n_accepted += 1;
+                        progress.accepted = true;
+                        progress.current_energy = new.energy;
+                        current = new;
+                    }
+                }
+                if !callback(&progress) {
+                    break;
+                }
+            }
+        }
+
+        let report = BasinHoppingReport {
+            atom_types: self.atom_types.clone(),
+            minima,
+            n_steps,
+            n_accepted,
+        };
+        Ok(report)
+    }
+
+    /// Random perturbation of `positions`: rotation of a fragment or
+    /// displacements of all atoms.
+    fn perturb(&self, positions: &[f64], rng: &mut StdRng) -> Result<Vec<f64>> {
+        let mut x = positions.to_vec();
+        let fragments = fragments(&self.atom_types, positions)?;
+        if fragments.len() > 1 && rng.gen::<f64>() < self.rotation_probability {
+            let atoms = &fragments[rng.gen_range(0..fragments.len())];
+            let center = atoms
+                .iter()
+                .fold(Vector3::zeros(), |c, &i| c + Vector3::new(x[3 * i], x[3 * i + 1], x[3 * i + 2]))
+                / atoms.len() as f64;
+            let axis = Vector3::from_fn(|_, _| StandardNormal.sample(rng));
+            let angle = rng.gen_range(-1.0..=1.0) * self.max_rotation.to_radians();
+            let rot = Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle);
+            for &i in atoms {
+                let r = rot * (Vector3::new(x[3 * i], x[3 * i + 1], x[3 * i + 2]) - center) + center;
+                x[3 * i..3 * i + 3].copy_from_slice(r.as_slice());
+            }
+        } else {
+            let d = self.max_displacement;
+            x.iter_mut().for_each(|xi| *xi += rng.gen_range(-d..=d));
+        }
+        Ok(x)
+    }
+
+    /// Metropolis criterion for energy change `de`.
+    fn metropolis(&self, de: f64, rng: &mut StdRng) -> bool {
+        if de <= 0.0 {
+            true
+        } else if self.temperature == 0.0 {
+            false
+        } else {
+            rng.gen::<f64>() < (-de / (KB * self.temperature)).exp()
+        }
+    }
+
+    /// Add `minimum` into `minima` sorted by energy, unless it duplicates a
+    /// known one.
+    fn archive(&self, minima: &mut Vec<Minimum>, minimum: &Minimum) {
+        let distances = sorted_distances(&self.atom_types, &minimum.positions);
+        let duplicated = minima.iter().any(|m| {
+            (m.energy - minimum.energy).abs() < self.energy_threshold
+                && (rmsd(&m.positions, &minimum.positions) < self.rmsd_threshold
+                    || rms_difference(&sorted_distances(&self.atom_types, &m.positions), &distances)
+                        < self.rmsd_threshold)
+        });
+        if !duplicated {
+            let i = minima.partition_point(|m| m.energy <= minimum.energy);
+            minima.insert(i, minimum.clone());
+            minima.truncate(self.max_minima);
+        }
+    }
+}
+
+/// Interatomic distances grouped by pairs of elements and sorted, which do
+/// not depend on the numbering of identical atoms.
+fn sorted_distances(atom_types: &[i32], positions: &[f64]) -> Vec<f64> {
+    let n = atom_types.len();
+    let mut pairs = vec![];
+    for i in 0..n {
+        for j in 0..i {
+            let (zi, zj) = (atom_types[i], atom_types[j]);
+            let r = (0..3).map(|x| (positions[3 * i + x] - positions[3 * j + x]).powi(2)).sum::<f64>().sqrt();
+            pairs.push(((zi.min(zj), zi.max(zj)), r));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    pairs.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Root mean square difference between `a` and `b`.
+fn rms_difference(a: &[f64], b: &[f64]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    let s: f64 = a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum();
+    (s / a.len() as f64).sqrt()
+}
+// 27f5c1b9 ends here
@@ -0,0 +1,473 @@
+// [[file:../xtb.note::81c4e6a9][81c4e6a9]]
+//! Conformer search by torsion-grid or dynamics sampling, and ranking of the
+//! conformer ensemble, similar to a minimal CREST workflow.
+// 81c4e6a9 ends here
+
+// [[file:../xtb.note::f25b9d03][f25b9d03]]
+use crate::elements::atomic_mass;
+use crate::internal::{covalent_bonds, covalent_radii, InternalCoordinate, LINEAR_ANGLE};
+use crate::md::{Dynamics, KB};
+use crate::opt::{Lbfgs, OptConvergence};
+use crate::potential::Potential;
+use crate::rmsd::rmsd;
+use crate::thermo::rotational_constants;
+use crate::xyz::write_xyz;
+
+use anyhow::*;
+use nalgebra::{Rotation3, Unit, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+// f25b9d03 ends here
+
+// [[file:../xtb.note::0b7e2d58][0b7e2d58]]
+/// Return neighbors of each atom from covalent bonds.
+fn neighbor_lists(natoms: usize, bonds: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut neighbors = vec![vec![]; natoms];
+    for &(i, j) in bonds {
+        neighbors[i].push(j);
+        neighbors[j].push(i);
+    }
+    neighbors
+}
+
+/// Return atoms connected to `k` without passing through bond j-k,
+/// including `k`. The result contains `j` if the bond is in a ring.
+fn bond_side(neighbors: &[Vec<usize>], j: usize, k: usize) -> Vec<usize> {
+    let mut side = vec![k];
+    let mut visited = vec![false; neighbors.len()];
+    visited[k] = true;
+    let mut n = 0;
+    while n < side.len() {
+        let a = side[n];
+        for &b in neighbors[a].iter() {
+            if !(visited[b] || a == k && b == j) {
+                visited[b] = true;
+                side.push(b);
+            }
+        }
+        n += 1;
+    }
+    side
+}
+
+/// Return rotatable bonds as zero-based atom pairs, detected from covalent
+/// connectivity of structure `coord` in Bohr. Bonds in rings, bonds to
+/// terminal atoms or to groups of only hydrogens such as methyl, bonds
+/// involving linear angles and bonds between two planar three-coordinated
+/// atoms (double bonds, amides) are not rotatable.
+pub fn rotatable_bonds(atom_types: &[i32], coord: &[f64]) -> Result<Vec<(usize, usize)>> {
+    let bonds = covalent_bonds(atom_types, coord)?;
+    let neighbors = neighbor_lists(atom_types.len(), &bonds);
+    let angle = |i, j, k| InternalCoordinate::Angle(i, j, k).value(coord).to_degrees();
+    let is_planar = |a: usize| {
+        let na = &neighbors[a];
+        na.len() == 3 && angle(na[0], a, na[1]) + angle(na[1], a, na[2]) + angle(na[0], a, na[2]) > 355.0
+    };
+    let is_rotor_end = |a: usize, b: usize| {
+        let others: Vec<_> = neighbors[a].iter().copied().filter(|&i| i != b).collect();
+        let hydrogens = others.len() > 1 && others.iter().all(|&i| atom_types[i] == 1);
+        !(others.is_empty() || hydrogens) && others.iter().all(|&i| angle(i, a, b) < LINEAR_ANGLE)
+    };
+
+    let rotatable = bonds
+        .into_iter()
+        .filter(|&(j, k)| {
+            is_rotor_end(j, k)
+                && is_rotor_end(k, j)
+                && !(is_planar(j) && is_planar(k))
+                && !bond_side(&neighbors, j, k).contains(&j)
+        })
+        .collect();
+    Ok(rotatable)
+}
+
+/// Rotate atoms in `side` of `positions` by `angle` in radians around the
+/// axis from atom `j` to `k`.
+fn rotate_side(positions: &mut [f64], j: usize, k: usize, side: &[usize], angle: f64) {
+    let r = |x: &[f64], i: usize| Vector3::new(x[3 * i], x[3 * i + 1], x[3 * i + 2]);
+    let origin = r(positions, k);
+    let axis = Unit::new_normalize(origin - r(positions, j));
+    let rot = Rotation3::from_axis_angle(&axis, angle);
+    for &i in side {
+        let p = rot * (r(positions, i) - origin) + origin;
+        positions[3 * i..3 * i + 3].copy_from_slice(p.as_slice());
+    }
+}
+
+/// Return true if any two atoms not bonded in `bonds` are closer than half
+/// of the sum of their covalent `radii`.
+fn has_clash(radii: &[f64], positions: &[f64], bonds: &[(usize, usize)]) -> bool {
+    (0..radii.len()).any(|i| {
+        (0..i).any(|j| {
+            !bonds.contains(&(j, i)) && InternalCoordinate::Bond(j, i).value(positions) < 0.5 * (radii[i] + radii[j])
+        })
+    })
+}
+// 0b7e2d58 ends here
+
+// [[file:../xtb.note::a6d14f7c][a6d14f7c]]
+/// Method for generating starting structures of conformers.
+#[derive(Clone, Debug)]
+enum Sampling {
+    /// Torsion grid with spacing in degrees
+    TorsionGrid(f64),
+    /// Snapshots of dynamics with number of steps and interval
+    Dynamics(Box<Dynamics>, usize, usize),
+}
+
+/// Conformer search: sample starting structures, optimize each of them,
+/// and rank the distinct conformers by energy. Optionally structures could
+/// be preoptimized with a cheap potential such as GFN-FF and refined with
+/// a more accurate one such as GFN2-xTB.
+///
+/// Two conformers are duplicates if their energy difference is below the
+/// threshold, and either their RMSD is below the threshold or their
+/// rotational constants agree within the relative threshold. The latter
+/// catches rotamers which differ only by permutation of identical atoms, as
+/// in CREST.
+#[derive(Clone, Debug)]
+pub struct ConformerSearch {
+    atom_types: Vec<i32>,
+    sampling: Sampling,
+    max_samples: usize,
+    energy_window: f64,
+    energy_threshold: f64,
+    rmsd_threshold: f64,
+    rotational_threshold: f64,
+    temperature: f64,
+    optimizer: Lbfgs,
+    seed: Option<u64>,
+}
+
+/// A conformer in the ensemble.
+#[derive(Clone, Debug)]
+pub struct Conformer {
+    /// Energy in Hartree
+    pub energy: f64,
+    /// Energy relative to the lowest conformer in Hartree
+    pub relative_energy: f64,
+    /// Boltzmann population at the ensemble temperature
+    pub weight: f64,
+    /// Rotational constants in cm^-1 in descending order
+    pub rotational_constants: [f64; 3],
+    /// Positions in Bohr
+    pub positions: Vec<f64>,
+}
+
+/// Conformer ensemble in ascending order of energy.
+#[derive(Clone, Debug)]
+pub struct ConformerEnsemble {
+    /// Atomic numbers of atoms
+    pub atom_types: Vec<i32>,
+    /// Distinct conformers in ascending order of energy
+    pub conformers: Vec<Conformer>,
+    /// Temperature in K for Boltzmann weights
+    pub temperature: f64,
+    /// Number of sampled starting structures
+    pub n_samples: usize,
+    /// Number of optimizations which failed or did not converge
+    pub n_failed: usize,
+}
+
+impl ConformerEnsemble {
+    /// Return the lowest conformer.
+    pub fn lowest(&self) -> &Conformer {
+        &self.conformers[0]
+    }
+
+    /// Return Boltzmann-weighted average energy in Hartree.
+    pub fn average_energy(&self) -> f64 {
+        self.conformers.iter().map(|c| c.weight * c.energy).sum()
+    }
+
+    /// Write all conformers into `path` in xyz format in ascending order of
+    /// energy. The comment line of each frame has energy, relative energy
+    /// in kcal/mol and Boltzmann weight.
+    pub fn write_xyz(&self, path: impl AsRef<Path>) -> Result<()> {
+        let frames = self.conformers.iter().map(|c| {
+            let comment = format!(
+                "energy= {:.10} erel= {:.4} weight= {:.4}",
+                c.energy,
+                c.relative_energy * 627.5095,
+                c.weight
+            );
+            (comment, c.positions.as_slice())
+        });
+        write_xyz(path, &self.atom_types, frames)
+    }
+}
+
+impl ConformerSearch {
+    /// Create conformer search for molecule with `atom_types` in atomic
+    /// numbers.
+    pub fn new(atom_types: &[i32]) -> Self {
+        Self {
+            atom_types: atom_types.to_vec(),
+            sampling: Sampling::TorsionGrid(120.0),
+            max_samples: 100,
+            // 6 kcal/mol, the same as CREST
+            energy_window: 6.0 / 627.5095,
+            energy_threshold: 0.05 / 627.5095,
+            rmsd_threshold: 0.25,
+            rotational_threshold: 0.01,
+            temperature: 298.15,
+            optimizer: Lbfgs::default(),
+            seed: None,
+        }
+    }
+
+    /// Sample by rotating all rotatable bonds through a grid of dihedral
+    /// increments with spacing `step` in degrees. This is the default with
+    /// 120 degrees.
+    pub fn torsion_grid(&mut self, step: f64) -> &mut Self {
+        assert!(step > 0.0, "invalid torsion step: {}", step);
+        self.sampling = Sampling::TorsionGrid(step);
+        self
+    }
+
+    /// Sample by taking snapshots every `interval` steps from `nsteps` of
+    /// `dynamics`, for example with a thermostat at elevated temperature.
+    pub fn dynamics(&mut self, dynamics: &Dynamics, nsteps: usize, interval: usize) -> &mut Self {
+        assert!(interval > 0, "invalid snapshot interval: {}", interval);
+        self.sampling = Sampling::Dynamics(Box::new(dynamics.clone()), nsteps, interval);
+        self
+    }
+
+    /// Set the largest number of sampled structures to optimize. Torsion
+    /// grids larger than this are sampled randomly, and dynamics stops once
+    /// enough snapshots are taken. The default is 100.
+    pub fn max_samples(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of samples: {}", n);
+        self.max_samples = n;
+        self
+    }
+
+    /// Keep conformers within energy window `e` in Hartree above the
+    /// lowest one, judged from refined energies with
+    /// [`run_refined`](Self::run_refined). The default is 6 kcal/mol.
+    pub fn energy_window(&mut self, e: f64) -> &mut Self {
+        assert!(e >= 0.0, "invalid energy window: {}", e);
+        self.energy_window = e;
+        self
+    }
+
+    /// Set thresholds of energy difference in Hartree, RMSD in Bohr and
+    /// relative difference of rotational constants for duplicates. The
+    /// defaults are 0.05 kcal/mol, 0.25 Bohr and 0.01.
+    pub fn duplicate_thresholds(&mut self, energy: f64, rmsd: f64, rotational: f64) -> &mut Self {
+        self.energy_threshold = energy;
+        self.rmsd_threshold = rmsd;
+        self.rotational_threshold = rotational;
+        self
+    }
+
+    /// Set temperature in K for Boltzmann weights. The default is 298.15 K.
+    pub fn temperature(&mut self, t: f64) -> &mut Self {
+        assert!(t > 0.0, "invalid temperature: {}", t);
+        self.temperature = t;
+        self
+    }
+
+    /// Set convergence criteria of optimizations.
+    pub fn convergence(&mut self, convergence: OptConvergence) -> &mut Self {
+        self.optimizer.convergence(convergence);
+        self
+    }
+
+    /// Set maximum number of steps of optimizations.
+    pub fn max_steps(&mut self, n: usize) -> &mut Self {
+        self.optimizer.max_steps(n);
+        self
+    }
+
+    /// Set seed of random number generator for reproducible runs.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Search conformers on `pot` starting from `positions` in Bohr.
+    /// Sampling and optimizations are all done with `pot`.
+    pub fn run<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<ConformerEnsemble> {
+        self.search::<P, P>(pot, None, positions)
+    }
+
+    /// Search conformers starting from `positions` in Bohr. Sampling and
+    /// preoptimization are done with the cheap potential `pre`, and the
+    /// distinct conformers are reoptimized with `pot`.
+    pub fn run_refined<P, Q>(&self, pre: &mut P, pot: &mut Q, positions: &[f64]) -> Result<ConformerEnsemble>
+    where
+        P: Potential + ?Sized,
+        Q: Potential + ?Sized,
+    {
+        self.search(pre, Some(pot), positions)
+    }
+
+    fn search<P, Q>(&self, pot: &mut P, refine: Option<&mut Q>, positions: &[f64]) -> Result<ConformerEnsemble>
+    where
+        P: Potential + ?Sized,
+        Q: Potential + ?Sized,
+    {
+        ensure!(
+            positions.len() == 3 * self.atom_types.len(),
+            "positions do not match atom types"
+        );
+        let masses: Vec<_> = self
+            .atom_types
+            .iter()
+            .map(|&z| atomic_mass(z).ok_or_else(|| format_err!("no atomic mass for element {z}")))
+            .collect::<Result<_>>()?;
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let start = self.optimizer.minimize(pot, positions)?.positions;
+        let samples = self.sample(pot, &start, &mut rng)?;
+        let n_samples = samples.len();
+
+        // failed optimizations are skipped and counted
+        let mut n_failed = 0;
+        let mut candidates = vec![];
+        for x in samples.iter() {
+            match self.optimizer.minimize(pot, x).ok().filter(|r| r.converged()) {
+                Some(report) => candidates.push((report.energy, report.positions)),
+                None => n_failed += 1,
+            }
+        }
+        // with refinement, the window only applies to refined energies, since
+        // the cheap potential may order conformers differently
+        let window = if refine.is_some() { f64::INFINITY } else { self.energy_window };
+        let mut conformers = self.rank(&masses, candidates, window);
+        if let Some(refine) = refine {
+            let mut candidates = vec![];
+            for c in conformers.iter() {
+                match self.optimizer.minimize(refine, &c.positions).ok().filter(|r| r.converged()) {
+                    Some(report) => candidates.push((report.energy, report.positions)),
+                    None => n_failed += 1,
+                }
+            }
+            conformers = self.rank(&masses, candidates, self.energy_window);
+        }
+        ensure!(!conformers.is_empty(), "no optimization of conformers converged");
+
+        let ensemble = ConformerEnsemble {
+            atom_types: self.atom_types.clone(),
+            conformers,
+            temperature: self.temperature,
+            n_samples,
+            n_failed,
+        };
+        Ok(ensemble)
+    }
+
+    /// Generate starting structures from optimized structure `start`,
+    /// which is always included.
+    fn sample<P: Potential + ?Sized>(&self, pot: &mut P, start: &[f64], rng: &mut StdRng) -> Result<Vec<Vec<f64>>> {
+        let mut samples = vec![start.to_vec()];
+        match &self.sampling {
+            Sampling::TorsionGrid(step) => {
+                let bonds = covalent_bonds(&self.atom_types, start)?;
+                let radii = covalent_radii(&self.atom_types)?;
+                let neighbors = neighbor_lists(self.atom_types.len(), &bonds);
+                // rotate the smaller side of each bond
+                let rotors: Vec<_> = rotatable_bonds(&self.atom_types, start)?
+                    .into_iter()
+                    .map(|(j, k)| {
+                        let side_k = bond_side(&neighbors, j, k);
+                        let side_j = bond_side(&neighbors, k, j);
+                        if side_k.len() <= side_j.len() {
+                            (j, k, side_k)
+                        } else {
+                            (k, j, side_j)
+                        }
+                    })
+                    .collect();
+                let m = ((360.0 / step).round() as usize).max(1);
+                let n_grid = u32::try_from(rotors.len())
+                    .ok()
+                    .and_then(|n| m.checked_pow(n))
+                    .unwrap_or(usize::MAX);
+                let grid: Vec<Vec<usize>> = if n_grid <= self.max_samples {
+                    // all grid points in mixed radix order
+                    (1..n_grid)
+                        .map(|mut p| {
+                            (0..rotors.len())
+                                .map(|_| {
+                                    let d = p % m;
+                                    p /= m;
+                                    d
+                                })
+                                .collect()
+                        })
+                        .collect()
+                } else {
+                    (1..self.max_samples)
+                        .map(|_| (0..rotors.len()).map(|_| rng.gen_range(0..m)).collect())
+                        .collect()
+                };
+                for point in grid {
+                    let mut x = start.to_vec();
+                    for ((j, k, side), &n) in rotors.iter().zip(&point) {
+                        rotate_side(&mut x, *j, *k, side, (n as f64 * step).to_radians());
+                    }
+                    if !has_clash(&radii, &x, &bonds) {
+                        samples.push(x);
+                    }
+                }
+            }
+            Sampling::Dynamics(dynamics, nsteps, interval) => {
+                let n = self.max_samples;
+                dynamics.run_with(pot, start, *nsteps, |s| {
+                    if s.step > 0 && s.step % *interval == 0 {
+                        samples.push(s.positions.to_vec());
+                    }
+                    samples.len() < n
+                })?;
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Remove duplicates and structures out of energy `window` from
+    /// optimized `candidates` of energy and positions, and compute Boltzmann
+    /// weights. Rotational constants are computed with atomic `masses`.
+    fn rank(&self, masses: &[f64], mut candidates: Vec<(f64, Vec<f64>)>, window: f64) -> Vec<Conformer> {
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut conformers: Vec<Conformer> = vec![];
+        for (energy, positions) in candidates {
+            let e0 = conformers.first().map_or(energy, |c| c.energy);
+            if energy - e0 > window {
+                break;
+            }
+            let rot = rotational_constants(masses, &positions);
+            let same_rotor = |c: &Conformer| {
+                c.rotational_constants
+                    .iter()
+                    .zip(&rot)
+                    .all(|(a, b)| a == b || (a - b).abs() <= self.rotational_threshold * a.max(*b))
+            };
+            let duplicated = conformers.iter().any(|c| {
+                energy - c.energy < self.energy_threshold
+                    && (same_rotor(c) || rmsd(&c.positions, &positions) < self.rmsd_threshold)
+            });
+            if !duplicated {
+                conformers.push(Conformer {
+                    energy,
+                    relative_energy: energy - e0,
+                    weight: 0.0,
+                    rotational_constants: rot,
+                    positions,
+                });
+            }
+        }
+
+        let kt = KB * self.temperature;
+        let z: f64 = conformers.iter().map(|c| (-c.relative_energy / kt).exp()).sum();
+        for c in conformers.iter_mut() {
+            c.weight = (-c.relative_energy / kt).exp() / z;
+        }
+        conformers
+    }
+}
+// a6d14f7c ends here
@@ -0,0 +1,230 @@
+// [[file:../xtb.note::5e1a7c3d][5e1a7c3d]]
+//! Frozen atoms and harmonic restraints on internal coordinates, similar to
+//! the `$fix` and `$constrain` blocks of xtb.
+// 5e1a7c3d ends here
+
+// [[file:../xtb.note::c9b2e064][c9b2e064]]
+use crate::internal::InternalCoordinate;
+use crate::potential::Potential;
+
+use anyhow::*;
+// c9b2e064 ends here
+
+// [[file:../xtb.note::3d8f6a17][3d8f6a17]]
+/// Harmonic restraint `k * (q - q0)^2` on an internal coordinate.
+#[derive(Clone, Debug)]
+pub struct Restraint {
+    /// The restrained internal coordinate
+    pub coord: InternalCoordinate,
+    /// Target value in Bohr or radians. None for the value in the first
+    /// evaluated structure.
+    pub target: Option<f64>,
+    /// Force constant in Hartree / Bohr^2 or Hartree / rad^2. None for the
+    /// default force constant of `Constraints`.
+    pub force_constant: Option<f64>,
+}
+
+/// Constraints applied on top of a potential: frozen atoms and harmonic
+/// restraints on bonds, angles and dihedrals.
+#[derive(Clone, Debug)]
+pub struct Constraints {
+    frozen: Vec<usize>,
+    restraints: Vec<Restraint>,
+    force_constant: f64,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self {
+            frozen: vec![],
+            restraints: vec![],
+            // the same as the default in xtb
+            force_constant: 0.05,
+        }
+    }
+}
+
+impl Constraints {
+    /// Set default force constant for restraints in atomic units. The
+    /// default is 0.05, the same as xtb.
+    pub fn force_constant(&mut self, k: f64) -> &mut Self {
+        assert!(k >= 0.0, "invalid force constant: {k}");
+        self.force_constant = k;
+        self
+    }
+
+    /// Freeze `atoms` (zero-based) at their positions, like `$fix` in xtb.
+    pub fn freeze_atoms(&mut self, atoms: &[usize]) -> &mut Self {
+        for &i in atoms {
+            if !self.frozen.contains(&i) {
+                self.frozen.push(i);
+            }
+        }
+        self
+    }
+
+    /// Restrain distance between atom `i` and `j` to `target` in Bohr. None
+    /// keeps the distance in the starting structure.
+    pub fn distance(&mut self, i: usize, j: usize, target: impl Into<Option<f64>>) -> &mut Self {
+        self.add(InternalCoordinate::Bond(i, j), target.into(), None)
+    }
+
+    /// Restrain angle i-j-k to `target` in degrees. None keeps the angle in
+    /// the starting structure.
+    pub fn angle(&mut self, i: usize, j: usize, k: usize, target: impl Into<Option<f64>>) -> &mut Self {
+        let target = target.into().map(f64::to_radians);
+        self.add(InternalCoordinate::Angle(i, j, k), target, None)
+    }
+
+    /// Restrain dihedral angle i-j-k-l to `target` in degrees. None keeps the
+    /// dihedral angle in the starting structure.
+    pub fn dihedral(&mut self, i: usize, j: usize, k: usize, l: usize, target: impl Into<Option<f64>>) -> &mut Self {
+        let target = target.into().map(f64::to_radians);
+        self.add(InternalCoordinate::Dihedral(i, j, k, l), target, None)
+    }
+
+    /// Add a general restraint on `coord` with `target` in Bohr or radians
+    /// and an individual force constant `k`.
+    pub fn restrain(
+        &mut self,
+        coord: InternalCoordinate,
+        target: impl Into<Option<f64>>,
+        k: impl Into<Option<f64>>,
+    ) -> &mut Self {
+        self.add(coord, target.into(), k.into())
+    }
+
+    fn add(&mut self, coord: InternalCoordinate, target: Option<f64>, force_constant: Option<f64>) -> &mut Self {
+        self.restraints.push(Restraint {
+            coord,
+            target,
+            force_constant,
+        });
+        self
+    }
+
+    /// Return frozen atoms.
+    pub fn frozen_atoms(&self) -> &[usize] {
+        &self.frozen
+    }
+
+    /// Return all restraints.
+    pub fn restraints(&self) -> &[Restraint] {
+        &self.restraints
+    }
+
+    /// Return true if no constraint is defined.
+    pub fn is_empty(&self) -> bool {
+        self.frozen.is_empty() && self.restraints.is_empty()
+    }
+
+    /// Fix undefined restraint targets to their values in `positions`.
+    pub fn set_targets_from(&mut self, positions: &[f64]) {
+        for r in self.restraints.iter_mut().filter(|r| r.target.is_none()) {
+            r.target = Some(r.coord.value(positions));
+        }
+    }
+
+    /// Check atom indices against the number of atoms.
+    pub fn check(&self, natoms: usize) -> Result<()> {
+        for &i in self.frozen.iter() {
+            ensure!(i < natoms, "invalid frozen atom {i} for {natoms} atoms");
+        }
+        for r in self.restraints.iter() {
+            let atoms = r.coord.atoms();
+            ensure!(atoms.iter().all(|&i| i < natoms), "invalid restraint {:?} for {natoms} atoms", r.coord);
+            for (n, i) in atoms.iter().enumerate() {
+                ensure!(!atoms[n + 1..].contains(i), "duplicated atoms in restraint {:?}", r.coord);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add restraint energy and its gradient at `positions` into `gradient`,
+    /// and project out gradient components on frozen atoms. Return the
+    /// restraint energy. Undefined targets are taken from `positions`.
+    pub fn apply(&mut self, positions: &[f64], gradient: &mut [f64]) -> f64 {
+        self.set_targets_from(positions);
+        let mut energy = 0.0;
+        for r in self.restraints.iter() {
+            let k = r.force_constant.unwrap_or(self.force_constant);
+            let q = r.coord.value(positions);
+            let dq = r.coord.difference(q, r.target.unwrap());
+            energy += k * dq * dq;
+            r.coord.add_gradient(positions, 2.0 * k * dq, gradient);
+        }
+        self.project(gradient);
+        energy
+    }
+
+    /// Zero components of `v` on frozen atoms. This could be applied on
+    /// gradients, velocities or displacements.
+    pub fn project(&self, v: &mut [f64]) {
+        for &i in self.frozen.iter() {
+            v[3 * i..3 * i + 3].fill(0.0);
+        }
+    }
+}
+// 3d8f6a17 ends here
+
+// [[file:../xtb.note::8a4f0d2b][8a4f0d2b]]
+/// A potential with constraints applied, which could be passed to any
+/// optimizer.
+pub struct ConstrainedPotential<P> {
+    potential: P,
+    constraints: Constraints,
+    restraint_energy: f64,
+    // number of atoms the constraints have been checked against
+    checked: Option<usize>,
+}
+
+impl<P: Potential> ConstrainedPotential<P> {
+    /// Apply `constraints` on `potential`.
+    pub fn new(potential: P, constraints: Constraints) -> Self {
+        Self {
+            potential,
+            constraints,
+            restraint_energy: 0.0,
+            checked: None,
+        }
+    }
+
+    /// Return the constraints.
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+
+    /// Return restraint energy in the last evaluation, which is included in
+    /// the total energy.
+    pub fn restraint_energy(&self) -> f64 {
+        self.restraint_energy
+    }
+
+    /// Return the wrapped potential.
+    pub fn inner(&mut self) -> &mut P {
+        &mut self.potential
+    }
+
+    /// Unwrap the potential.
+    pub fn into_inner(self) -> P {
+        self.potential
+    }
+}
+
+impl<P: Potential> Potential for ConstrainedPotential<P> {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        let natoms = positions.len() / 3;
+        if self.checked != Some(natoms) {
+            self.constraints.check(natoms)?;
+            self.checked = Some(natoms);
+        }
+        let energy = self.potential.evaluate(positions, gradient)?;
+        self.restraint_energy = self.constraints.apply(positions, gradient);
+        Ok(energy + self.restraint_energy)
+    }
+
+    fn frozen_atoms(&self) -> &[usize] {
+        self.constraints.frozen_atoms()
+    }
+}
+// 8a4f0d2b ends here
@@ -0,0 +1,79 @@
+// [[file:../xtb.note::a6d3f1e8][a6d3f1e8]]
+//! Element data indexed by atomic number
+// a6d3f1e8 ends here
+
+// [[file:../xtb.note::2e9c7b50][2e9c7b50]]
+/// Conversion factor from Angstrom to Bohr.
+pub const ANGSTROM_TO_BOHR: f64 = 1.0 / 0.52917721092;
+
+/// Single bond covalent radii in Angstrom for H-Rn, from P. Pyykkö and M.
+/// Atsumi, Chem. Eur. J. 15, 186 (2009).
+const COVALENT_RADII: [f64; 86] = [
+    0.32, 0.46, // H-He
+    1.33, 1.02, 0.85, 0.75, 0.71, 0.63, 0.64, 0.67, // Li-Ne
+    1.55, 1.39, 1.26, 1.16, 1.11, 1.03, 0.99, 0.96, // Na-Ar
+    1.96, 1.71, 1.48, 1.36, 1.34, 1.22, 1.19, 1.16, 1.11, 1.10, 1.12, 1.18, // K-Zn
+    1.24, 1.21, 1.21, 1.16, 1.14, 1.17, // Ga-Kr
+    2.10, 1.85, 1.63, 1.54, 1.47, 1.38, 1.28, 1.25, 1.25, 1.20, 1.28, 1.36, // Rb-Cd
+    1.42, 1.40, 1.40, 1.36, 1.33, 1.31, // In-Xe
+    2.32, 1.96, // Cs-Ba
+    1.80, 1.63, 1.76, 1.74, 1.73, 1.72, 1.68, 1.69, 1.68, 1.67, 1.66, 1.65, 1.64, 1.70, 1.62, // La-Lu
+    1.52, 1.46, 1.37, 1.31, 1.29, 1.22, 1.23, 1.24, 1.33, // Hf-Hg
+    1.44, 1.44, 1.51, 1.45, 1.47, 1.42, // Tl-Rn
+];
+
+/// Return single bond covalent radius in Bohr for element with atomic number `z`.
+pub fn covalent_radius(z: i32) -> Option<f64> {
+    let i = usize::try_from(z).ok()?.checked_sub(1)?;
+    COVALENT_RADII.get(i).map(|r| r * ANGSTROM_TO_BOHR)
+}
+// 2e9c7b50 ends here
+
+// [[file:../xtb.note::9b41c6d7][9b41c6d7]]
+/// Standard atomic weights in atomic mass units for H-Rn, averaged over
+/// natural isotopic abundance. Mass numbers of the most stable isotopes are
+/// used for elements without stable isotopes.
+const ATOMIC_MASSES: [f64; 86] = [
+    1.008, 4.0026, // H-He
+    6.94, 9.0122, 10.81, 12.011, 14.007, 15.999, 18.998, 20.180, // Li-Ne
+    22.990, 24.305, 26.982, 28.085, 30.974, 32.06, 35.45, 39.948, // Na-Ar
+    39.098, 40.078, 44.956, 47.867, 50.942, 51.996, 54.938, 55.845, 58.933, 58.693, 63.546, 65.38, // K-Zn
+    69.723, 72.630, 74.922, 78.971, 79.904, 83.798, // Ga-Kr
+    85.468, 87.62, 88.906, 91.224, 92.906, 95.95, 98.0, 101.07, 102.91, 106.42, 107.87, 112.41, // Rb-Cd
+    114.82, 118.71, 121.76, 127.60, 126.90, 131.29, // In-Xe
+    132.91, 137.33, // Cs-Ba
+    138.91, 140.12, 140.91, 144.24, 145.0, 150.36, 151.96, 157.25, 158.93, 162.50, 164.93, 167.26, 168.93, 173.05,
+    174.97, // La-Lu
+    178.49, 180.95, 183.84, 186.21, 190.23, 192.22, 195.08, 196.97, 200.59, // Hf-Hg
+    204.38, 207.2, 208.98, 209.0, 210.0, 222.0, // Tl-Rn
+];
+
+/// Return atomic mass in atomic mass units for element with atomic number `z`.
+pub fn atomic_mass(z: i32) -> Option<f64> {
+    let i = usize::try_from(z).ok()?.checked_sub(1)?;
+    ATOMIC_MASSES.get(i).copied()
+}
+// 9b41c6d7 ends here
+
+// [[file:../xtb.note::f0c6a2b5][f0c6a2b5]]
+/// Element symbols for H-Rn.
+const ELEMENT_SYMBOLS: [&str; 86] = [
+    "H", "He", // H-He
+    "Li", "Be", "B", "C", "N", "O", "F", "Ne", // Li-Ne
+    "Na", "Mg", "Al", "Si", "P", "S", "Cl", "Ar", // Na-Ar
+    "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", // K-Zn
+    "Ga", "Ge", "As", "Se", "Br", "Kr", // Ga-Kr
+    "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", // Rb-Cd
+    "In", "Sn", "Sb", "Te", "I", "Xe", // In-Xe
+    "Cs", "Ba", // Cs-Ba
+    "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu", // La-Lu
+    "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", // Hf-Hg
+    "Tl", "Pb", "Bi", "Po", "At", "Rn", // Tl-Rn
+];
+
+/// Return element symbol for element with atomic number `z`.
+pub fn element_symbol(z: i32) -> Option<&'static str> {
+    let i = usize::try_from(z).ok()?.checked_sub(1)?;
+    ELEMENT_SYMBOLS.get(i).copied()
+}
+// f0c6a2b5 ends here
@@ -0,0 +1,322 @@
+// [[file:../xtb.note::4f8a2d63][4f8a2d63]]
+//! Numerical Hessian by finite differences of gradients, and harmonic
+//! vibrational analysis
+// 4f8a2d63 ends here
+
+// [[file:../xtb.note::e0c5b7a4][e0c5b7a4]]
+use crate::elements::atomic_mass;
+use crate::potential::Potential;
+use crate::thermo::{is_linear, principal_axes};
+
+use anyhow::*;
+use nalgebra::{DMatrix, DVector};
+// e0c5b7a4 ends here
+
+// [[file:../xtb.note::2a6f9e15][2a6f9e15]]
+/// Conversion factor from sqrt(Hartree / (Bohr^2 amu)) to wavenumber in cm^-1.
+pub(crate) const AU_TO_WAVENUMBER: f64 = 5140.487;
+
+/// Numerical Hessian from central finite differences of gradients.
+#[derive(Clone, Debug)]
+pub struct NumericalHessian {
+    step: f64,
+    nthreads: usize,
+}
+
+impl Default for NumericalHessian {
+    fn default() -> Self {
+        Self {
+            // the same as xtb
+            step: 0.005,
+            nthreads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl NumericalHessian {
+    /// Set displacement step in Bohr. The default is 0.005 Bohr.
+    pub fn step_size(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid step size: {}", x);
+        self.step = x;
+        self
+    }
+
+    /// Set the number of threads for `compute_parallel`. The default is
+    /// the available parallelism of the system.
+    pub fn threads(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of threads: {}", n);
+        self.nthreads = n;
+        self
+    }
+
+    /// Compute Hessian of `pot` at `positions` in Bohr. Return the
+    /// symmetrized Hessian matrix in Hartree / Bohr^2, stored in row-major
+    /// order of size 3N x 3N.
+    pub fn compute<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<Vec<f64>> {
+        check_positions(positions)?;
+        let rows = (0..positions.len())
+            .map(|k| self.derivative(positions, k, |x| gradient_of(pot, x)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(symmetrize(rows))
+    }
+
+    /// Compute Hessian at `positions` with displacements evaluated in
+    /// parallel. Each thread evaluates gradients using its own potential
+    /// created by `create`, for example an `XtbModel` created with the same
+    /// parameters.
+    pub fn compute_parallel<P, F>(&self, create: F, positions: &[f64]) -> Result<Vec<f64>>
+    where
+        P: Potential,
+        F: Fn() -> Result<P> + Sync,
+    {
+        check_positions(positions)?;
+        let rows = self.derivatives_parallel(positions, &create, gradient_of)?;
+        Ok(symmetrize(rows))
+    }
+
+    /// Central difference of `f` with respect to coordinate `k`.
+    pub(crate) fn derivative<F>(&self, positions: &[f64], k: usize, mut f: F) -> Result<Vec<f64>>
+    where
+        F: FnMut(&[f64]) -> Result<Vec<f64>>,
+    {
+        let mut x = positions.to_vec();
+        x[k] = positions[k] + self.step;
+        let fp = f(&x)?;
+        x[k] = positions[k] - self.step;
+        let fm = f(&x)?;
+        let d = fp.iter().zip(fm).map(|(a, b)| (a - b) / (2.0 * self.step)).collect();
+        Ok(d)
+    }
+
+    /// Central differences of `f` for all coordinates, evaluated in parallel
+    /// threads with states created by `create`.
+    pub(crate) fn derivatives_parallel<S, C, F>(&self, positions: &[f64], create: &C, f: F) -> Result<Vec<Vec<f64>>>
+    where
+        C: Fn() -> Result<S> + Sync,
+        F: Fn(&mut S, &[f64]) -> Result<Vec<f64>> + Sync,
+    {
+        let n = positions.len();
+        let nthreads = self.nthreads.min(n);
+        let f = &f;
+        let parts = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..nthreads)
+                .map(|t| {
+                    s.spawn(move || -> Result<Vec<(usize, Vec<f64>)>> {
+                        let mut state = create()?;
+                        (t..n)
+                            .step_by(nthreads)
+                            .map(|k| Ok((k, self.derivative(positions, k, |x| f(&mut state, x))?)))
+                            .collect()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().map_err(|_| format_err!("thread for finite differences panicked"))?)
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut rows = vec![vec![]; n];
+        for (k, row) in parts.into_iter().flatten() {
+            rows[k] = row;
+        }
+        Ok(rows)
+    }
+}
+
+pub(crate) fn check_positions(positions: &[f64]) -> Result<()> {
+    let n = positions.len();
+    ensure!(
+        n > 0 && positions.chunks_exact(3).remainder().is_empty(),
+        "invalid size of positions: {}",
+        n
+    );
+    Ok(())
+}
+
+fn gradient_of<P: Potential + ?Sized>(pot: &mut P, positions: &[f64]) -> Result<Vec<f64>> {
+    let mut gradient = vec![0.0; positions.len()];
+    pot.evaluate(positions, &mut gradient)?;
+    Ok(gradient)
+}
+
+pub(crate) fn symmetrize(rows: Vec<Vec<f64>>) -> Vec<f64> {
+    let n = rows.len();
+    let mut hessian = vec![0.0; n * n];
+    for i in 0..n {
+        for j in 0..n {
+            hessian[i * n + j] = 0.5 * (rows[i][j] + rows[j][i]);
+        }
+    }
+    hessian
+}
+// 2a6f9e15 ends here
+
+// [[file:../xtb.note::c3d71b8e][c3d71b8e]]
+/// Harmonic vibrational analysis with translations and rotations projected
+/// out.
+#[derive(Clone, Debug)]
+pub struct VibrationalAnalysis {
+    masses: Vec<f64>,
+    imaginary_cutoff: f64,
+}
+
+/// Normal modes from harmonic vibrational analysis.
+#[derive(Clone, Debug)]
+pub struct NormalModes {
+    /// Harmonic frequencies in cm^-1 in ascending order. Imaginary
+    /// frequencies are given as negative numbers.
+    pub frequencies: Vec<f64>,
+    /// Normalized Cartesian displacements of each mode
+    pub modes: Vec<Vec<f64>>,
+    /// Reduced masses of each mode in amu
+    pub reduced_masses: Vec<f64>,
+    /// Whether the molecule is linear
+    pub linear: bool,
+    /// Threshold in cm^-1 below which imaginary frequencies are regarded
+    /// as numerical noise
+    pub imaginary_cutoff: f64,
+}
+
+impl NormalModes {
+    /// Return the number of imaginary frequencies beyond the cutoff.
+    pub fn n_imaginary(&self) -> usize {
+        self.frequencies.iter().filter(|&&f| f < -self.imaginary_cutoff).count()
+    }
+}
+
+impl VibrationalAnalysis {
+    /// Use atomic masses of `atom_types` for mass-weighting.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        let masses = atom_types
+            .iter()
+            .map(|&z| atomic_mass(z).ok_or_else(|| format_err!("no atomic mass for element {z}")))
+            .collect::<Result<_>>()?;
+        let vib = Self {
+            masses,
+            imaginary_cutoff: 20.0,
+        };
+        Ok(vib)
+    }
+
+    /// Override mass of atom `i` (zero-based) in amu, for example for
+    /// isotopic substitution.
+    pub fn mass(&mut self, i: usize, mass: f64) -> &mut Self {
+        assert!(mass > 0.0, "invalid mass: {}", mass);
+        self.masses[i] = mass;
+        self
+    }
+
+    /// Set threshold in cm^-1 below which imaginary frequencies are
+    /// regarded as numerical noise rather than counted as imaginary modes.
+    /// The default is 20 cm^-1, the same as xtb.
+    pub fn imaginary_cutoff(&mut self, x: f64) -> &mut Self {
+        assert!(x >= 0.0, "invalid imaginary cutoff: {}", x);
+        self.imaginary_cutoff = x;
+        self
+    }
+
+    /// Return masses of all atoms in amu.
+    pub fn masses(&self) -> &[f64] {
+        &self.masses
+    }
+
+    /// Compute normal modes from Cartesian `hessian` in Hartree / Bohr^2
+    /// (row-major 3N x 3N) at `positions` in Bohr.
+    pub fn normal_modes(&self, positions: &[f64], hessian: &[f64]) -> Result<NormalModes> {
+        let n = 3 * self.masses.len();
+        ensure!(positions.len() == n, "positions do not match {} atoms", self.masses.len());
+        ensure!(hessian.len() == n * n, "invalid size of hessian: {}", hessian.len());
+
+        let sqrt_m: Vec<_> = self.masses.iter().flat_map(|&m| [m.sqrt(); 3]).collect();
+        let hessian_mw = DMatrix::from_fn(n, n, |i, j| hessian[i * n + j] / (sqrt_m[i] * sqrt_m[j]));
+
+        // orthonormal basis of vibrational space
+        let (tr, linear) = translations_rotations(&self.masses, positions);
+        let mut projector = DMatrix::<f64>::identity(n, n);
+        for v in tr.iter() {
+            projector -= v * v.transpose();
+        }
+        let eigen = projector.symmetric_eigen();
+        let columns: Vec<_> = (0..n)
+            .filter(|&i| eigen.eigenvalues[i] > 0.5)
+            .map(|i| eigen.eigenvectors.column(i).into_owned())
+            .collect();
+        let mut frequencies = vec![];
+        let mut modes = vec![];
+        let mut reduced_masses = vec![];
+        if !columns.is_empty() {
+            let basis = DMatrix::from_columns(&columns);
+            let h = basis.transpose() * &hessian_mw * &basis;
+            let eigen = h.symmetric_eigen();
+            let mut order: Vec<_> = (0..eigen.eigenvalues.len()).collect();
+            order.sort_by(|&a, &b| eigen.eigenvalues[a].total_cmp(&eigen.eigenvalues[b]));
+            for i in order {
+                let e = eigen.eigenvalues[i];
+                frequencies.push(e.signum() * e.abs().sqrt() * AU_TO_WAVENUMBER);
+                let mode_mw = &basis * eigen.eigenvectors.column(i);
+                let mut mode: Vec<_> = mode_mw.iter().zip(&sqrt_m).map(|(l, m)| l / m).collect();
+                let norm2: f64 = mode.iter().map(|x| x * x).sum();
+                reduced_masses.push(1.0 / norm2);
+                mode.iter_mut().for_each(|x| *x /= norm2.sqrt());
+                modes.push(mode);
+            }
+        }
+
+        let modes = NormalModes {
+            frequencies,
+            modes,
+            reduced_masses,
+            linear,
+            imaginary_cutoff: self.imaginary_cutoff,
+        };
+        Ok(modes)
+    }
+}
+
+/// Orthonormal mass-weighted translation and rotation vectors. Also return
+/// true if the molecule is linear.
+pub(crate) fn translations_rotations(masses: &[f64], positions: &[f64]) -> (Vec<DVector<f64>>, bool) {
+    let n = positions.len();
+    let mtot: f64 = masses.iter().sum();
+    let mut com = [0.0; 3];
+    for (m, r) in masses.iter().zip(positions.chunks_exact(3)) {
+        for x in 0..3 {
+            com[x] += m * r[x] / mtot;
+        }
+    }
+
+    let mut candidates = vec![];
+    for x in 0..3 {
+        candidates.push(DVector::from_fn(n, |i, _| if i % 3 == x { masses[i / 3].sqrt() } else { 0.0 }));
+    }
+    // rotations around principal axes, skipping the one along a linear
+    // molecule
+    let linear = is_linear(masses, positions);
+    let (_, axes) = principal_axes(masses, positions);
+    let first = if linear { 1 } else { 0 };
+    for u in axes.iter().skip(first) {
+        // rotation around axis u: u cross r
+        let v = DVector::from_fn(n, |i, _| {
+            let a = i / 3;
+            let r = [0, 1, 2].map(|k| positions[3 * a + k] - com[k]);
+            let (y, z) = ((i + 1) % 3, (i + 2) % 3);
+            masses[a].sqrt() * (u[y] * r[z] - u[z] * r[y])
+        });
+        candidates.push(v);
+    }
+
+    // Gram-Schmidt orthonormalization, dropping dependent vectors
+    let mut basis: Vec<DVector<f64>> = vec![];
+    for mut v in candidates {
+        for b in basis.iter() {
+            v -= b * b.dot(&v);
+        }
+        let norm = v.norm();
+        if norm > 1e-6 * mtot.sqrt() {
+            basis.push(v / norm);
+        }
+    }
+    (basis, linear)
+}
+// c3d71b8e ends here
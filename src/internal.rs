@@ -0,0 +1,567 @@
+// [[file:../xtb.note::7a3c91e5][7a3c91e5]]
+//! Internal coordinates (bonds, angles and dihedrals), their derivatives and
+//! redundant internal coordinate sets
+// 7a3c91e5 ends here
+
+// [[file:../xtb.note::d08f4b6a][d08f4b6a]]
+use crate::elements::covalent_radius;
+
+use anyhow::*;
+use nalgebra::{DMatrix, DVector};
+use std::f64::consts::PI;
+// d08f4b6a ends here
+
+// [[file:../xtb.note::4c6e2f19][4c6e2f19]]
+type Vector3 = [f64; 3];
+
+fn sub(a: Vector3, b: Vector3) -> Vector3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot3(a: Vector3, b: Vector3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn scale(a: Vector3, s: f64) -> Vector3 {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn position(coord: &[f64], i: usize) -> Vector3 {
+    [coord[3 * i], coord[3 * i + 1], coord[3 * i + 2]]
+}
+
+/// Wrap angle difference `x` in radians into [-pi, pi).
+pub(crate) fn wrap_angle(x: f64) -> f64 {
+    (x + PI).rem_euclid(2.0 * PI) - PI
+}
+// 4c6e2f19 ends here
+
+// [[file:../xtb.note::b5e9d2c7][b5e9d2c7]]
+/// Primitive internal coordinate defined by zero-based atom indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InternalCoordinate {
+    /// Distance between atom i and j
+    Bond(usize, usize),
+    /// Angle i-j-k with j as the apex
+    Angle(usize, usize, usize),
+    /// Dihedral angle i-j-k-l around the j-k bond
+    Dihedral(usize, usize, usize, usize),
+    /// Bending of nearly linear angle i-j-k projected on Cartesian axis (0,
+    /// 1 or 2), measured as sum of unit vectors from j to i and k.
+    LinearBend(usize, usize, usize, usize),
+}
+
+impl InternalCoordinate {
+    /// Return atoms involved in this coordinate.
+    pub fn atoms(&self) -> Vec<usize> {
+        match *self {
+            InternalCoordinate::Bond(i, j) => vec![i, j],
+            InternalCoordinate::Angle(i, j, k) => vec![i, j, k],
+            InternalCoordinate::Dihedral(i, j, k, l) => vec![i, j, k, l],
+            InternalCoordinate::LinearBend(i, j, k, _) => vec![i, j, k],
+        }
+    }
+
+    /// Return true for angular coordinates which are dimensionless.
+    pub fn is_angular(&self) -> bool {
+        !matches!(self, InternalCoordinate::Bond(..))
+    }
+
+    /// Return true if the coordinate is periodic (dihedral).
+    pub fn is_periodic(&self) -> bool {
+        matches!(self, InternalCoordinate::Dihedral(..))
+    }
+
+    /// Evaluate value of this coordinate for Cartesian positions `coord`.
+    /// Distances are in the unit of `coord`, and angles in radians. The
+    /// dihedral angle is in the range of [-pi, pi].
+    pub fn value(&self, coord: &[f64]) -> f64 {
+        match *self {
+            InternalCoordinate::Bond(i, j) => {
+                let d = sub(position(coord, i), position(coord, j));
+                dot3(d, d).sqrt()
+            }
+            InternalCoordinate::Angle(i, j, k) => {
+                let u = sub(position(coord, i), position(coord, j));
+                let v = sub(position(coord, k), position(coord, j));
+                let c = dot3(u, v) / (dot3(u, u) * dot3(v, v)).sqrt();
+                c.clamp(-1.0, 1.0).acos()
+            }
+            InternalCoordinate::Dihedral(i, j, k, l) => {
+                let b1 = sub(position(coord, j), position(coord, i));
+                let b2 = sub(position(coord, k), position(coord, j));
+                let b3 = sub(position(coord, l), position(coord, k));
+                let n1 = cross(b1, b2);
+                let n2 = cross(b2, b3);
+                let y = dot3(b2, b2).sqrt() * dot3(b1, n2);
+                let x = dot3(n1, n2);
+                y.atan2(x)
+            }
+            InternalCoordinate::LinearBend(i, j, k, axis) => {
+                let u = sub(position(coord, i), position(coord, j));
+                let w = sub(position(coord, k), position(coord, j));
+                u[axis] / dot3(u, u).sqrt() + w[axis] / dot3(w, w).sqrt()
+            }
+        }
+    }
+
+    /// Return the difference `a - b` of two values of this coordinate, taking
+    /// periodicity of dihedral angles into account.
+    pub fn difference(&self, a: f64, b: f64) -> f64 {
+        if self.is_periodic() {
+            wrap_angle(a - b)
+        } else {
+            a - b
+        }
+    }
+
+    /// Evaluate derivatives of this coordinate with respect to Cartesian
+    /// positions of the involved atoms, in the same order as `atoms()`.
+    /// This is a row of the Wilson B matrix.
+    pub fn derivatives(&self, coord: &[f64]) -> Vec<Vector3> {
+        match *self {
+            InternalCoordinate::Bond(i, j) => {
+                let d = sub(position(coord, i), position(coord, j));
+                let u = scale(d, 1.0 / dot3(d, d).sqrt());
+                vec![u, scale(u, -1.0)]
+            }
+            InternalCoordinate::Angle(i, j, k) => {
+                let u = sub(position(coord, i), position(coord, j));
+                let v = sub(position(coord, k), position(coord, j));
+                let lu = dot3(u, u).sqrt();
+                let lv = dot3(v, v).sqrt();
+                let (u, v) = (scale(u, 1.0 / lu), scale(v, 1.0 / lv));
+                let c = dot3(u, v).clamp(-1.0, 1.0);
+                // avoid singularity of linear angle
+                let s = (1.0 - c * c).sqrt().max(1e-8);
+                let di = scale(sub(scale(u, c), v), 1.0 / (lu * s));
+                let dk = scale(sub(scale(v, c), u), 1.0 / (lv * s));
+                let dj = scale([di[0] + dk[0], di[1] + dk[1], di[2] + dk[2]], -1.0);
+                vec![di, dj, dk]
+            }
+            InternalCoordinate::Dihedral(i, j, k, l) => {
+                let f = sub(position(coord, i), position(coord, j));
+                let g = sub(position(coord, j), position(coord, k));
+                let h = sub(position(coord, l), position(coord, k));
+                let a = cross(f, g);
+                let b = cross(h, g);
+                let aa = dot3(a, a).max(1e-16);
+                let bb = dot3(b, b).max(1e-16);
+                let lg = dot3(g, g).sqrt();
+                let fg = dot3(f, g);
+                let hg = dot3(h, g);
+                let di = scale(a, -lg / aa);
+                let dl = scale(b, lg / bb);
+                let dj = sub(scale(a, lg / aa + fg / (aa * lg)), scale(b, hg / (bb * lg)));
+                let dk = sub(scale(b, hg / (bb * lg) - lg / bb), scale(a, fg / (aa * lg)));
+                vec![di, dj, dk, dl]
+            }
+            InternalCoordinate::LinearBend(i, j, k, axis) => {
+                let mut e = [0.0; 3];
+                e[axis] = 1.0;
+                let u = sub(position(coord, i), position(coord, j));
+                let w = sub(position(coord, k), position(coord, j));
+                let lu = dot3(u, u).sqrt();
+                let lw = dot3(w, w).sqrt();
+                let (u, w) = (scale(u, 1.0 / lu), scale(w, 1.0 / lw));
+                let di = scale(sub(e, scale(u, u[axis])), 1.0 / lu);
+                let dk = scale(sub(e, scale(w, w[axis])), 1.0 / lw);
+                let dj = scale([di[0] + dk[0], di[1] + dk[1], di[2] + dk[2]], -1.0);
+                vec![di, dj, dk]
+            }
+        }
+    }
+
+    /// Add derivatives of this coordinate scaled by `factor` into Cartesian
+    /// gradient `gradient`.
+    pub fn add_gradient(&self, coord: &[f64], factor: f64, gradient: &mut [f64]) {
+        for (a, d) in self.atoms().into_iter().zip(self.derivatives(coord)) {
+            for x in 0..3 {
+                gradient[3 * a + x] += factor * d[x];
+            }
+        }
+    }
+}
+// b5e9d2c7 ends here
+
+// [[file:../xtb.note::63e1c8af][63e1c8af]]
+/// Angles larger than this are treated as linear, in degrees.
+pub(crate) const LINEAR_ANGLE: f64 = 175.0;
+
+/// Return covalently bonded atom pairs, based on the sum of covalent radii
+/// scaled by 1.3. `coord` is in Bohr.
+pub fn covalent_bonds(atom_types: &[i32], coord: &[f64]) -> Result<Vec<(usize, usize)>> {
+    let natoms = atom_types.len();
+    ensure!(coord.len() == 3 * natoms, "invalid size of coord: {}", coord.len());
+    let radii = covalent_radii(atom_types)?;
+    let mut bonds = vec![];
+    for i in 0..natoms {
+        for j in 0..i {
+            let r = InternalCoordinate::Bond(j, i).value(coord);
+            if r < 1.3 * (radii[i] + radii[j]) {
+                bonds.push((j, i));
+            }
+        }
+    }
+    Ok(bonds)
+}
+
+/// Return covalently bonded fragments as lists of atom indices in
+/// ascending order, ordered by their first atom. `coord` is in Bohr.
+pub fn fragments(atom_types: &[i32], coord: &[f64]) -> Result<Vec<Vec<usize>>> {
+    let natoms = atom_types.len();
+    let mut sets = DisjointSets::new(natoms);
+    for (i, j) in covalent_bonds(atom_types, coord)? {
+        sets.union(i, j);
+    }
+    let mut fragments: Vec<Vec<usize>> = vec![];
+    let mut index = vec![0; natoms];
+    for i in 0..natoms {
+        let r = sets.root(i);
+        if r == i {
+            index[i] = fragments.len();
+            fragments.push(vec![i]);
+        } else {
+            fragments[index[r]].push(i);
+        }
+    }
+    Ok(fragments)
+}
+
+/// Union-find of atoms connected by bonds. The root of each set is its
+/// smallest atom index.
+struct DisjointSets {
+    parent: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    fn root(&self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, i: usize, j: usize) {
+        let (ri, rj) = (self.root(i), self.root(j));
+        self.parent[ri.max(rj)] = ri.min(rj);
+    }
+}
+
+pub(crate) fn covalent_radii(atom_types: &[i32]) -> Result<Vec<f64>> {
+    atom_types
+        .iter()
+        .map(|&z| covalent_radius(z).ok_or_else(|| format_err!("no covalent radius for element {z}")))
+        .collect()
+}
+
+/// Generalized inverse of symmetric matrix `m`, ignoring eigenvalues below
+/// `threshold`.
+pub(crate) fn pseudo_inverse(m: DMatrix<f64>, threshold: f64) -> DMatrix<f64> {
+    let eigen = m.symmetric_eigen();
+    let inv = eigen.eigenvalues.map(|x| if x > threshold { 1.0 / x } else { 0.0 });
+    &eigen.eigenvectors * DMatrix::from_diagonal(&inv) * eigen.eigenvectors.transpose()
+}
+
+/// A set of redundant internal coordinates for a molecule, built from
+/// covalent connectivity. Separated fragments are connected by their
+/// closest atom pairs, and chains of linear angles are handled with linear
+/// bends and dihedrals spanning the whole chain.
+#[derive(Clone, Debug)]
+pub struct RedundantInternals {
+    coords: Vec<InternalCoordinate>,
+    radii: Vec<f64>,
+    // atoms excluded from the Wilson B matrix
+    frozen: Vec<usize>,
+}
+
+impl RedundantInternals {
+    /// Build internal coordinates for molecule with `atom_types` and
+    /// Cartesian coordinates `coord` in Bohr.
+    pub fn new(atom_types: &[i32], coord: &[f64]) -> Result<Self> {
+        let natoms = atom_types.len();
+        let radii = covalent_radii(atom_types)?;
+        let mut bonds = covalent_bonds(atom_types, coord)?;
+
+        // connect separated fragments with the closest atom pairs
+        let mut sets = DisjointSets::new(natoms);
+        for &(i, j) in bonds.iter() {
+            sets.union(i, j);
+        }
+        loop {
+            let mut closest: Option<(usize, usize, f64)> = None;
+            for i in 0..natoms {
+                for j in 0..i {
+                    if sets.root(i) != sets.root(j) {
+                        let r = InternalCoordinate::Bond(j, i).value(coord);
+                        if closest.map_or(true, |(_, _, rmin)| r < rmin) {
+                            closest = Some((j, i, r));
+                        }
+                    }
+                }
+            }
+            match closest {
+                Some((i, j, _)) => {
+                    bonds.push((i, j));
+                    sets.union(i, j);
+                }
+                None => break,
+            }
+        }
+
+        let mut neighbors = vec![vec![]; natoms];
+        for &(i, j) in bonds.iter() {
+            neighbors[i].push(j);
+            neighbors[j].push(i);
+        }
+        let is_linear = |i: usize, j: usize, k: usize| {
+            InternalCoordinate::Angle(i, j, k).value(coord).to_degrees() > LINEAR_ANGLE
+        };
+
+        let mut coords: Vec<_> = bonds.iter().map(|&(i, j)| InternalCoordinate::Bond(i, j)).collect();
+        for (j, nj) in neighbors.iter().enumerate() {
+            for (n, &i) in nj.iter().enumerate() {
+                for &k in nj[n + 1..].iter() {
+                    if is_linear(i, j, k) {
+                        // bend on two Cartesian axes most perpendicular to the linear chain
+                        let d = [0, 1, 2].map(|x| (coord[3 * k + x] - coord[3 * i + x]).abs());
+                        let mut axes = [0, 1, 2];
+                        axes.sort_by(|&a, &b| d[a].total_cmp(&d[b]));
+                        coords.push(InternalCoordinate::LinearBend(i, j, k, axes[0]));
+                        coords.push(InternalCoordinate::LinearBend(i, j, k, axes[1]));
+                    } else {
+                        coords.push(InternalCoordinate::Angle(i, j, k));
+                    }
+                }
+            }
+            // out-of-plane motion of planar center
+            if let [a, b, c] = nj[..] {
+                let sum = InternalCoordinate::Angle(a, j, b).value(coord)
+                    + InternalCoordinate::Angle(b, j, c).value(coord)
+                    + InternalCoordinate::Angle(a, j, c).value(coord);
+                if sum.to_degrees() > 355.0 {
+                    coords.push(InternalCoordinate::Dihedral(a, b, j, c));
+                }
+            }
+        }
+
+        // follow linear chain starting from bond `prev`-`j` to its end
+        let chain_end = |mut prev: usize, mut j: usize| {
+            while let Some(&n) = neighbors[j].iter().find(|&&n| n != prev && is_linear(prev, j, n)) {
+                prev = j;
+                j = n;
+            }
+            (prev, j)
+        };
+        let mut dihedrals = std::collections::HashSet::new();
+        for &(b, c) in bonds.iter() {
+            let (cn, j) = chain_end(c, b);
+            let (bn, k) = chain_end(b, c);
+            if j == k {
+                continue;
+            }
+            for &i in neighbors[j].iter().filter(|&&i| i != cn && i != k && !is_linear(i, j, cn)) {
+                for &l in neighbors[k].iter().filter(|&&l| l != bn && l != j && l != i && !is_linear(bn, k, l)) {
+                    let d = if i < l { (i, j, k, l) } else { (l, k, j, i) };
+                    if dihedrals.insert(d) {
+                        coords.push(InternalCoordinate::Dihedral(d.0, d.1, d.2, d.3));
+                    }
+                }
+            }
+        }
+
+        let mut internals = Self {
+            coords,
+            radii,
+            frozen: vec![],
+        };
+        // fall back to all interatomic distances if some degrees of freedom are missing
+        let nfree = match natoms {
+            1 => 0,
+            2 => 1,
+            _ => 3 * natoms - 6,
+        };
+        if internals.rank(coord) < nfree {
+            for i in 0..natoms {
+                for j in 0..i {
+                    if !bonds.contains(&(j, i)) && !bonds.contains(&(i, j)) {
+                        internals.coords.push(InternalCoordinate::Bond(j, i));
+                    }
+                }
+            }
+        }
+
+        Ok(internals)
+    }
+
+    /// Return all internal coordinates.
+    pub fn coords(&self) -> &[InternalCoordinate] {
+        &self.coords
+    }
+
+    /// Return the number of internal coordinates.
+    pub fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    /// Return true if there is no internal coordinate.
+    pub fn is_empty(&self) -> bool {
+        self.coords.is_empty()
+    }
+
+    /// Evaluate values of all internal coordinates at `coord`.
+    pub fn values(&self, coord: &[f64]) -> Vec<f64> {
+        self.coords.iter().map(|q| q.value(coord)).collect()
+    }
+
+    /// Return differences `a - b` of two sets of internal coordinate values.
+    pub fn difference(&self, a: &[f64], b: &[f64]) -> Vec<f64> {
+        self.coords.iter().zip(a.iter().zip(b)).map(|(q, (a, b))| q.difference(*a, *b)).collect()
+    }
+
+    /// Keep `atoms` (zero-based) fixed: their columns are dropped from the
+    /// Wilson B matrix, so internal steps and gradients only involve the
+    /// remaining atoms.
+    pub(crate) fn freeze_atoms(&mut self, atoms: &[usize]) {
+        self.frozen = atoms.to_vec();
+    }
+
+    /// Return Wilson B matrix at `coord`.
+    pub(crate) fn wilson_b(&self, coord: &[f64]) -> DMatrix<f64> {
+        let mut b = DMatrix::zeros(self.coords.len(), coord.len());
+        for (r, q) in self.coords.iter().enumerate() {
+            for (a, d) in q.atoms().into_iter().zip(q.derivatives(coord)) {
+                if self.frozen.contains(&a) {
+                    continue;
+                }
+                for x in 0..3 {
+                    b[(r, 3 * a + x)] += d[x];
+                }
+            }
+        }
+        b
+    }
+
+    fn rank(&self, coord: &[f64]) -> usize {
+        let b = self.wilson_b(coord);
+        let g = &b * b.transpose();
+        g.symmetric_eigen().eigenvalues.iter().filter(|&&x| x > 1e-6).count()
+    }
+
+    /// Diagonal model Hessian of Swart and Lindh type in atomic units, see
+    /// M. Swart and F. M. Bickelhaupt, Int. J. Quantum Chem. 106, 2536
+    /// (2006).
+    pub fn model_hessian(&self, coord: &[f64]) -> Vec<f64> {
+        let rho = |i: usize, j: usize| {
+            let r = InternalCoordinate::Bond(i, j).value(coord);
+            (1.0 - r / (self.radii[i] + self.radii[j])).exp()
+        };
+        self.coords
+            .iter()
+            .map(|q| match *q {
+                InternalCoordinate::Bond(i, j) => 0.35 * rho(i, j),
+                InternalCoordinate::Angle(i, j, k) | InternalCoordinate::LinearBend(i, j, k, _) => {
+                    0.15 * rho(i, j) * rho(j, k)
+                }
+                InternalCoordinate::Dihedral(i, j, k, l) => 0.005 * rho(i, j) * rho(j, k) * rho(k, l),
+            })
+            .map(|k| k.max(1e-4))
+            .collect()
+    }
+
+    /// Transform gradient in Cartesian coordinates into internal coordinates.
+    pub(crate) fn internal_gradient(&self, coord: &[f64], gradient: &[f64]) -> DVector<f64> {
+        let b = self.wilson_b(coord);
+        let g_inv = pseudo_inverse(&b * b.transpose(), 1e-6);
+        g_inv * (b * DVector::from_column_slice(gradient))
+    }
+
+    /// Find Cartesian displacement from `coord` that changes internal
+    /// coordinates by `dq` using iterative back-transformation. Return None if
+    /// the iteration diverges.
+    pub(crate) fn cartesian_step(&self, coord: &[f64], dq: &[f64]) -> Option<Vec<f64>> {
+        let q0 = self.values(coord);
+        let target: Vec<_> = q0.iter().zip(dq).map(|(q, d)| q + d).collect();
+        let mut x = coord.to_vec();
+        let mut remaining = dq.to_vec();
+        let mut first = None;
+        let mut last_error = f64::MAX;
+        for _ in 0..50 {
+            let b = self.wilson_b(&x);
+            let g_inv = pseudo_inverse(&b * b.transpose(), 1e-6);
+            let dx = b.transpose() * (g_inv * DVector::from_column_slice(&remaining));
+            x.iter_mut().zip(dx.iter()).for_each(|(x, d)| *x += d);
+            if first.is_none() {
+                first = Some(x.clone());
+            }
+            let error = dx.amax();
+            if error < 1e-8 {
+                break;
+            }
+            if error > last_error {
+                // use the first order step if the iteration diverges
+                x = first?;
+                break;
+            }
+            last_error = error;
+            remaining = self.difference(&target, &self.values(&x));
+        }
+        let step: Vec<_> = x.iter().zip(coord).map(|(a, b)| a - b).collect();
+        step.iter().all(|d| d.is_finite()).then_some(step)
+    }
+}
+// 63e1c8af ends here
+
+// [[file:../xtb.note::0f7b3d82][0f7b3d82]]
+#[test]
+fn test_internal_derivatives() {
+    use approx::assert_relative_eq;
+
+    let coord = [
+        0.1, 0.2, -0.1, //
+        2.8, 0.0, 0.3, //
+        3.5, 2.6, -0.2, //
+        5.9, 3.1, 1.7, //
+    ];
+    let coords = [
+        InternalCoordinate::Bond(0, 1),
+        InternalCoordinate::Angle(0, 1, 2),
+        InternalCoordinate::Dihedral(0, 1, 2, 3),
+        InternalCoordinate::Dihedral(3, 2, 1, 0),
+        InternalCoordinate::LinearBend(0, 1, 2, 0),
+        InternalCoordinate::LinearBend(1, 2, 3, 2),
+    ];
+    for q in coords.iter() {
+        let mut gradient = [0.0; 12];
+        q.add_gradient(&coord, 1.0, &mut gradient);
+        for i in 0..12 {
+            let h = 1e-5;
+            let mut cp = coord;
+            let mut cm = coord;
+            cp[i] += h;
+            cm[i] -= h;
+            let fd = q.difference(q.value(&cp), q.value(&cm)) / (2.0 * h);
+            assert_relative_eq!(gradient[i], fd, epsilon = 1e-6);
+        }
+    }
+
+    // IUPAC sign convention of dihedral angle
+    let coord = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0];
+    let phi = InternalCoordinate::Dihedral(0, 1, 2, 3).value(&coord);
+    assert_relative_eq!(phi, PI / 2.0, epsilon = 1e-9);
+    let phi = InternalCoordinate::Dihedral(3, 2, 1, 0).value(&coord);
+    assert_relative_eq!(phi, PI / 2.0, epsilon = 1e-9);
+}
+// 0f7b3d82 ends here
@@ -0,0 +1,215 @@
+// [[file:../xtb.note::0b7e4c2d][0b7e4c2d]]
+//! IR intensities from finite difference dipole derivatives, and broadened
+//! IR spectra
+// 0b7e4c2d ends here
+
+// [[file:../xtb.note::95c8a1f3][95c8a1f3]]
+use crate::hessian::{check_positions, symmetrize, NormalModes, NumericalHessian};
+use crate::xtb::XtbModel;
+
+use anyhow::*;
+use std::io::Write;
+use std::path::Path;
+// 95c8a1f3 ends here
+
+// [[file:../xtb.note::3f2d6b80][3f2d6b80]]
+/// Conversion factor of IR intensity from e^2 / amu to km/mol.
+const AU_TO_KM_PER_MOL: f64 = 974.88;
+
+/// Hessian and dipole derivatives from the same finite displacements.
+#[derive(Clone, Debug)]
+pub struct HessianDipoles {
+    /// Hessian in Hartree / Bohr^2, in row-major order of size 3N x 3N
+    pub hessian: Vec<f64>,
+    /// Derivatives of dipole moment in e with respect to each Cartesian
+    /// coordinate, in row-major order of size 3N x 3
+    pub dipole_derivatives: Vec<f64>,
+}
+
+// gradient followed by dipole moment
+fn gradient_and_dipole(xtb: &mut XtbModel, positions: &[f64]) -> Result<Vec<f64>> {
+    xtb.update_structure(positions, None)?;
+    let output = xtb.calculate()?;
+    let mut v = output.gradient;
+    v.extend_from_slice(&output.dipole);
+    Ok(v)
+}
+
+fn split_rows(rows: Vec<Vec<f64>>) -> HessianDipoles {
+    let n = rows.len();
+    let dipole_derivatives = rows.iter().flat_map(|row| row[n..].to_vec()).collect();
+    let rows = rows.into_iter().map(|row| row[..n].to_vec()).collect();
+    HessianDipoles {
+        hessian: symmetrize(rows),
+        dipole_derivatives,
+    }
+}
+
+impl NumericalHessian {
+    /// Compute Hessian together with dipole derivatives by displacing each
+    /// atom of `xtb` from `positions` in Bohr.
+    pub fn compute_with_dipole(&self, xtb: &mut XtbModel, positions: &[f64]) -> Result<HessianDipoles> {
+        check_positions(positions)?;
+        let rows = (0..positions.len())
+            .map(|k| self.derivative(positions, k, |x| gradient_and_dipole(xtb, x)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(split_rows(rows))
+    }
+
+    /// Compute Hessian together with dipole derivatives in parallel, each
+    /// thread using its own `XtbModel` created by `create`.
+    pub fn compute_with_dipole_parallel<F>(&self, create: F, positions: &[f64]) -> Result<HessianDipoles>
+    where
+        F: Fn() -> Result<XtbModel> + Sync,
+    {
+        check_positions(positions)?;
+        let rows = self.derivatives_parallel(positions, &create, gradient_and_dipole)?;
+        Ok(split_rows(rows))
+    }
+}
+// 3f2d6b80 ends here
+
+// [[file:../xtb.note::c81f5e27][c81f5e27]]
+/// Shape of broadened spectral lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineShape {
+    Lorentzian,
+    Gaussian,
+}
+
+/// Settings for broadening of line spectrum. Each line is broadened with
+/// unit area, so that the integrated band equals the intensity.
+#[derive(Clone, Debug)]
+pub struct Broadening {
+    shape: LineShape,
+    fwhm: f64,
+    range: [f64; 2],
+    step: f64,
+}
+
+impl Default for Broadening {
+    fn default() -> Self {
+        Self {
+            shape: LineShape::Lorentzian,
+            fwhm: 30.0,
+            range: [0.0, 4000.0],
+            step: 1.0,
+        }
+    }
+}
+
+impl Broadening {
+    /// Set line shape. The default is Lorentzian.
+    pub fn shape(&mut self, shape: LineShape) -> &mut Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Set full width at half maximum in cm^-1. The default is 30 cm^-1.
+    pub fn width(&mut self, fwhm: f64) -> &mut Self {
+        assert!(fwhm > 0.0, "invalid line width: {}", fwhm);
+        self.fwhm = fwhm;
+        self
+    }
+
+    /// Set range of wavenumbers in cm^-1. The default is 0-4000 cm^-1.
+    pub fn range(&mut self, min: f64, max: f64) -> &mut Self {
+        assert!(min < max, "invalid range: {} - {}", min, max);
+        self.range = [min, max];
+        self
+    }
+
+    /// Set grid spacing in cm^-1. The default is 1 cm^-1.
+    pub fn step(&mut self, step: f64) -> &mut Self {
+        assert!(step > 0.0, "invalid step: {}", step);
+        self.step = step;
+        self
+    }
+
+    fn line(&self, x: f64, x0: f64) -> f64 {
+        let dx = x - x0;
+        match self.shape {
+            LineShape::Lorentzian => {
+                let gamma = 0.5 * self.fwhm;
+                gamma / (std::f64::consts::PI * (dx * dx + gamma * gamma))
+            }
+            LineShape::Gaussian => {
+                let sigma = self.fwhm / (8.0 * 2f64.ln()).sqrt();
+                (-0.5 * (dx / sigma).powi(2)).exp() / (sigma * (2.0 * std::f64::consts::PI).sqrt())
+            }
+        }
+    }
+}
+
+/// IR line spectrum of normal modes.
+#[derive(Clone, Debug)]
+pub struct IrSpectrum {
+    /// Harmonic frequencies in cm^-1
+    pub frequencies: Vec<f64>,
+    /// IR intensities in km/mol
+    pub intensities: Vec<f64>,
+}
+
+impl IrSpectrum {
+    /// Compute IR intensities of normal `modes` from Cartesian
+    /// `dipole_derivatives` (3N x 3, see `HessianDipoles`).
+    pub fn new(modes: &NormalModes, dipole_derivatives: &[f64]) -> Result<Self> {
+        let mut intensities = vec![];
+        for (mode, mu) in modes.modes.iter().zip(&modes.reduced_masses) {
+            ensure!(
+                dipole_derivatives.len() == 3 * mode.len(),
+                "invalid size of dipole derivatives: {}",
+                dipole_derivatives.len()
+            );
+            // derivative with respect to mass-weighted normal coordinate
+            let mut dq = [0.0; 3];
+            for (k, l) in mode.iter().enumerate() {
+                for x in 0..3 {
+                    dq[x] += dipole_derivatives[3 * k + x] * l;
+                }
+            }
+            let d2: f64 = dq.iter().map(|d| d * d).sum::<f64>() / mu;
+            intensities.push(d2 * AU_TO_KM_PER_MOL);
+        }
+        let spectrum = Self {
+            frequencies: modes.frequencies.clone(),
+            intensities,
+        };
+        Ok(spectrum)
+    }
+
+    /// Return broadened spectrum as (wavenumber, intensity) points.
+    /// Imaginary modes are excluded.
+    pub fn broaden(&self, broadening: &Broadening) -> Vec<[f64; 2]> {
+        let [min, max] = broadening.range;
+        let npoints = ((max - min) / broadening.step).floor() as usize + 1;
+        (0..npoints)
+            .map(|i| {
+                let x = min + i as f64 * broadening.step;
+                let y = self
+                    .frequencies
+                    .iter()
+                    .zip(&self.intensities)
+                    .filter(|(f, _)| **f > 0.0)
+                    .map(|(f, a)| a * broadening.line(x, *f))
+                    .sum();
+                [x, y]
+            })
+            .collect()
+    }
+
+    /// Write broadened spectrum into `path` as two-column data of
+    /// wavenumber in cm^-1 and intensity in km/mol per cm^-1.
+    pub fn write_broadened(&self, path: impl AsRef<Path>, broadening: &Broadening) -> Result<()> {
+        let path = path.as_ref();
+        let f = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut f = std::io::BufWriter::new(f);
+        writeln!(f, "# wavenumber/cm-1  intensity/(km/mol/cm-1)")?;
+        for [x, y] in self.broaden(broadening) {
+            writeln!(f, "{:12.4} {:16.8e}", x, y)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+}
+// c81f5e27 ends here
@@ -0,0 +1,329 @@
+// [[file:../xtb.note::2c7e5b91][2c7e5b91]]
+//! Intrinsic reaction coordinate (IRC) following from a transition state
+// 2c7e5b91 ends here
+
+// [[file:../xtb.note::8f4a1d36][8f4a1d36]]
+use crate::hessian::{translations_rotations, VibrationalAnalysis};
+use crate::opt::Lbfgs;
+use crate::potential::Potential;
+use crate::ts::bofill_update;
+use crate::utils::*;
+use crate::xyz::write_xyz;
+
+use anyhow::*;
+use nalgebra::DMatrix;
+use std::path::Path;
+// 8f4a1d36 ends here
+
+// [[file:../xtb.note::d59b3e07][d59b3e07]]
+/// Intrinsic reaction coordinate in mass-weighted Cartesian coordinates,
+/// integrated with the local quadratic approximation (LQA) of Page and
+/// McIver (J. Chem. Phys. 88, 922 (1988)). The Hessian at the transition
+/// state is updated with the Bofill formula along the path.
+#[derive(Clone, Debug)]
+pub struct Irc {
+    atom_types: Vec<i32>,
+    vibrations: VibrationalAnalysis,
+    step_size: f64,
+    max_points: usize,
+    max_gradient: f64,
+    optimize_endpoints: bool,
+}
+
+/// A point on the reaction path.
+#[derive(Clone, Debug)]
+pub struct IrcPoint {
+    /// Arc length from the transition state in sqrt(amu) Bohr, negative
+    /// in the backward direction
+    pub s: f64,
+    /// Energy in Hartree
+    pub energy: f64,
+    /// Positions in Bohr
+    pub positions: Vec<f64>,
+}
+
+/// Reaction path in both directions from the transition state.
+#[derive(Clone, Debug)]
+pub struct IrcReport {
+    /// Atomic numbers of atoms
+    pub atom_types: Vec<i32>,
+    /// Points from the end of the backward path through the transition
+    /// state to the end of the forward path
+    pub points: Vec<IrcPoint>,
+    /// Index of the transition state in `points`
+    pub ts_index: usize,
+    /// Whether the forward path reached a minimum
+    pub forward_converged: bool,
+    /// Whether the backward path reached a minimum
+    pub backward_converged: bool,
+}
+
+impl IrcReport {
+    /// Return the transition state.
+    pub fn ts(&self) -> &IrcPoint {
+        &self.points[self.ts_index]
+    }
+
+    /// Return the last point in the forward direction.
+    pub fn forward_end(&self) -> &IrcPoint {
+        &self.points[self.points.len() - 1]
+    }
+
+    /// Return the last point in the backward direction.
+    pub fn backward_end(&self) -> &IrcPoint {
+        &self.points[0]
+    }
+
+    /// Write all points into `path` in xyz format with coordinates in
+    /// Angstrom. The comment line of each frame has arc length and energy.
+    pub fn write_xyz(&self, path: impl AsRef<Path>) -> Result<()> {
+        let frames = self
+            .points
+            .iter()
+            .map(|p| (format!("s= {:.6} energy= {:.10}", p.s, p.energy), p.positions.as_slice()));
+        write_xyz(path, &self.atom_types, frames)
+    }
+}
+
+impl Irc {
+    /// Create IRC for molecule with `atom_types` in atomic numbers.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        let irc = Self {
+            atom_types: atom_types.to_vec(),
+            vibrations: VibrationalAnalysis::new(atom_types)?,
+            step_size: 0.1,
+            max_points: 100,
+            max_gradient: 5e-4,
+            optimize_endpoints: true,
+        };
+        Ok(irc)
+    }
+
+    /// Set step size in sqrt(amu) Bohr. The default is 0.1.
+    pub fn step_size(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid step size: {}", x);
+        self.step_size = x;
+        self
+    }
+
+    /// Set maximum number of points in each direction. The default is 100.
+    pub fn max_points(&mut self, n: usize) -> &mut Self {
+        self.max_points = n;
+        self
+    }
+
+    /// Set threshold of the largest gradient component in Hartree / Bohr
+    /// for stopping at minimum. The default is 5e-4.
+    pub fn max_gradient(&mut self, x: f64) -> &mut Self {
+        self.max_gradient = x;
+        self
+    }
+
+    /// Enable or disable optimization of the last point in each direction
+    /// to the minimum. The default is enabled.
+    pub fn optimize_endpoints(&mut self, optimize: bool) -> &mut Self {
+        self.optimize_endpoints = optimize;
+        self
+    }
+
+    /// Follow reaction path of `pot` in both directions from transition
+    /// state at `positions` in Bohr with Cartesian `hessian` in Hartree /
+    /// Bohr^2 (row-major 3N x 3N, see `NumericalHessian`). The forward
+    /// direction follows the phase of the imaginary mode from
+    /// `VibrationalAnalysis`.
+    pub fn run<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64], hessian: &[f64]) -> Result<IrcReport> {
+        let modes = self.vibrations.normal_modes(positions, hessian)?;
+        ensure!(modes.n_imaginary() > 0, "no imaginary frequency at transition state");
+        // transition vector in mass-weighted coordinates
+        let masses = self.vibrations.masses();
+        let mut v: Vec<_> = modes.modes[0].iter().enumerate().map(|(i, l)| l * masses[i / 3].sqrt()).collect();
+        let vnorm = norm(&v);
+        v.iter_mut().for_each(|x| *x /= vnorm);
+
+        let n = positions.len();
+        let mut g = vec![0.0; n];
+        let energy = pot.evaluate(positions, &mut g)?;
+        let ts = IrcPoint {
+            s: 0.0,
+            energy,
+            positions: positions.to_vec(),
+        };
+        let hessian = DMatrix::from_row_slice(n, n, hessian);
+        let (backward, backward_converged) = self.follow(pot, &ts, &g, &hessian, &v, -1.0)?;
+        let (forward, forward_converged) = self.follow(pot, &ts, &g, &hessian, &v, 1.0)?;
+
+        let ts_index = backward.len();
+        let points = backward.into_iter().rev().chain(Some(ts)).chain(forward).collect();
+        let report = IrcReport {
+            atom_types: self.atom_types.clone(),
+            points,
+            ts_index,
+            forward_converged,
+            backward_converged,
+        };
+        Ok(report)
+    }
+
+    /// Follow path from `ts` with gradient `g` and `hessian` along the
+    /// transition vector `v` with `sign`.
+    fn follow<P: Potential + ?Sized>(
+        &self,
+        pot: &mut P,
+        ts: &IrcPoint,
+        g: &[f64],
+        hessian: &DMatrix<f64>,
+        v: &[f64],
+        sign: f64,
+    ) -> Result<(Vec<IrcPoint>, bool)> {
+        let n = ts.positions.len();
+        let sqrt_m: Vec<_> = self.vibrations.masses().iter().flat_map(|&m| [m.sqrt(); 3]).collect();
+        let mut hessian = hessian.clone();
+        let mut points = vec![];
+        let mut converged = false;
+
+        // initial step along the transition vector
+        let mut x = ts.positions.clone();
+        let mut g = g.to_vec();
+        let mut energy = ts.energy;
+        let mut d: Vec<_> = v.iter().zip(&sqrt_m).map(|(vi, m)| sign * self.step_size * vi / m).collect();
+        let mut s = 0.0;
+        let mut h = self.step_size;
+        let mut g_new = vec![0.0; n];
+        while points.len() < self.max_points {
+            let x_new: Vec<_> = x.iter().zip(&d).map(|(a, b)| a + b).collect();
+            let e_new = pot.evaluate(&x_new, &mut g_new)?;
+            if e_new > energy && !points.is_empty() {
+                // passed over the minimum: retry with a shorter step, and
+                // give up if the energy cannot be lowered any more
+                h *= 0.5;
+                if h < 1e-3 * self.step_size {
+                    break;
+                }
+                d = self.lqa_step(&x, &g, &hessian, &sqrt_m, h).0;
+                continue;
+            }
+            let y: Vec<_> = g_new.iter().zip(&g).map(|(a, b)| a - b).collect();
+            bofill_update(&mut hessian, &d, &y);
+            s += sign * d.iter().zip(&sqrt_m).map(|(a, m)| (a * m).powi(2)).sum::<f64>().sqrt();
+            x = x_new;
+            g.clone_from(&g_new);
+            energy = e_new;
+            points.push(IrcPoint {
+                s,
+                energy,
+                positions: x.clone(),
+            });
+            // the gradient is also small near the transition state
+            let (step, lowest) = self.lqa_step(&x, &g, &hessian, &sqrt_m, h);
+            if max_abs(&g) < self.max_gradient && lowest > -1e-8 {
+                converged = true;
+                break;
+            }
+            d = step;
+        }
+
+        if self.optimize_endpoints {
+            let report = Lbfgs::default().minimize(pot, &x)?;
+            converged = report.converged();
+            let dq: f64 = report.positions.iter().zip(&x).zip(&sqrt_m).map(|((a, b), m)| ((a - b) * m).powi(2)).sum();
+            if dq.sqrt() > 1e-4 {
+                points.push(IrcPoint {
+                    s: s + sign * dq.sqrt(),
+                    energy: report.energy,
+                    positions: report.positions,
+                });
+            }
+        }
+        Ok((points, converged))
+    }
+
+    /// Cartesian displacement of one LQA step of arc length `h` at
+    /// `positions` with `gradient` and Cartesian `hessian`. Also return the
+    /// lowest eigenvalue of mass-weighted Hessian.
+    fn lqa_step(
+        &self,
+        positions: &[f64],
+        gradient: &[f64],
+        hessian: &DMatrix<f64>,
+        sqrt_m: &[f64],
+        h: f64,
+    ) -> (Vec<f64>, f64) {
+        let n = positions.len();
+        let hessian_mw = DMatrix::from_fn(n, n, |i, j| hessian[(i, j)] / (sqrt_m[i] * sqrt_m[j]));
+        let (tr, _) = translations_rotations(self.vibrations.masses(), positions);
+        let mut projector = DMatrix::<f64>::identity(n, n);
+        for t in tr.iter() {
+            projector -= t * t.transpose();
+        }
+        let eigen = (&projector * hessian_mw * &projector).symmetric_eigen();
+        let gq: Vec<_> = gradient.iter().zip(sqrt_m).map(|(g, m)| g / m).collect();
+        let lambda: Vec<_> = eigen.eigenvalues.iter().copied().collect();
+        let gi: Vec<_> = (0..n)
+            .map(|i| eigen.eigenvectors.column(i).iter().zip(&gq).map(|(v, g)| v * g).sum())
+            .collect();
+
+        let t = lqa_time(&lambda, &gi, h);
+        let mut dq = vec![0.0; n];
+        for i in 0..n {
+            let c = if (lambda[i] * t).abs() < 1e-8 {
+                -gi[i] * t
+            } else {
+                gi[i] * ((-lambda[i] * t).exp() - 1.0) / lambda[i]
+            };
+            dq.iter_mut().zip(eigen.eigenvectors.column(i).iter()).for_each(|(d, v)| *d += c * v);
+        }
+        let d = dq.iter().zip(sqrt_m).map(|(d, m)| d / m).collect();
+        (d, lambda.iter().copied().fold(f64::INFINITY, f64::min))
+    }
+}
+
+/// Find the parameter t at which the steepest descent path of the quadratic
+/// model with eigenvalues `lambda` and gradient components `gi` reaches arc
+/// length `h`. Return a large t if the path ends at a stationary point
+/// before that.
+fn lqa_time(lambda: &[f64], gi: &[f64], h: f64) -> f64 {
+    let speed = |t: f64| -> f64 {
+        lambda
+            .iter()
+            .zip(gi)
+            .map(|(l, g)| g * g * (-2.0 * l * t).exp())
+            .sum::<f64>()
+            .sqrt()
+    };
+    // arc length by Simpson's rule
+    let arc = |t: f64| -> f64 {
+        let m = 200;
+        let dt = t / m as f64;
+        (0..=m)
+            .map(|k| {
+                let w = if k == 0 || k == m { 1.0 } else if k % 2 == 1 { 4.0 } else { 2.0 };
+                w * speed(k as f64 * dt)
+            })
+            .sum::<f64>()
+            * dt
+            / 3.0
+    };
+
+    let g0 = speed(0.0);
+    if g0 == 0.0 {
+        return 0.0;
+    }
+    let mut hi = h / g0;
+    while arc(hi) < h {
+        hi *= 2.0;
+        if hi > 1e8 {
+            return hi;
+        }
+    }
+    let mut lo = 0.0;
+    for _ in 0..60 {
+        let mid = 0.5 * (lo + hi);
+        if arc(mid) < h {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+// d59b3e07 ends here
@@ -14,11 +14,32 @@ use anyhow::*;
 // 0a60241b ends here
 
 // [[file:../xtb.note::b6996cbf][b6996cbf]]
+mod potential;
 mod raw;
+mod utils;
 mod xtb;
+mod xyz;
+
+pub mod basin;
+pub mod conformer;
+pub mod constraints;
+pub mod elements;
+pub mod hessian;
+pub mod internal;
+pub mod ir;
+pub mod irc;
+pub mod md;
+pub mod metadyn;
+pub mod neb;
+pub mod opt;
+pub mod rmsd;
+pub mod scan;
+pub mod thermo;
+pub mod ts;
 // b6996cbf ends here
 
 // [[file:../xtb.note::12b11409][12b11409]]
+pub use crate::potential::*;
 pub use crate::xtb::*;
 
 /// Low level wrapper for xtb api
@@ -0,0 +1,546 @@
+// [[file:../xtb.note::8e6d1f04][8e6d1f04]]
+//! Born-Oppenheimer molecular dynamics with velocity Verlet integrator and
+//! thermostats
+// 8e6d1f04 ends here
+
+// [[file:../xtb.note::4a2c9b7e][4a2c9b7e]]
+use crate::elements::atomic_mass;
+use crate::internal::covalent_bonds;
+use crate::potential::Potential;
+
+use anyhow::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{ChiSquared, Distribution, StandardNormal};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+// 4a2c9b7e ends here
+
+// [[file:../xtb.note::f17b3c58][f17b3c58]]
+/// Atomic units of time in one femtosecond.
+pub(crate) const FS_TO_AU: f64 = 41.341373;
+/// Electron masses in one atomic mass unit.
+pub(crate) const AMU_TO_AU: f64 = 1822.888486;
+/// Boltzmann constant in Hartree / K.
+pub(crate) const KB: f64 = 3.166811563e-6;
+
+/// Thermostat for controlling temperature in dynamics.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thermostat {
+    /// Microcanonical ensemble without temperature control
+    Nve,
+    /// Berendsen weak coupling with target temperature in K and coupling
+    /// time in fs
+    Berendsen { temperature: f64, tau: f64 },
+    /// Canonical sampling through velocity rescaling of Bussi, Donadio and
+    /// Parrinello, with target temperature in K and coupling time in fs
+    Bussi { temperature: f64, tau: f64 },
+    /// Langevin dynamics with target temperature in K and friction
+    /// coefficient in 1/fs
+    Langevin { temperature: f64, friction: f64 },
+}
+
+impl Thermostat {
+    fn temperature(&self) -> Option<f64> {
+        match *self {
+            Thermostat::Nve => None,
+            Thermostat::Berendsen { temperature, .. }
+            | Thermostat::Bussi { temperature, .. }
+            | Thermostat::Langevin { temperature, .. } => Some(temperature),
+        }
+    }
+}
+
+/// Bond length constraints with SHAKE/RATTLE algorithm. Bonds are detected
+/// from covalent radii in the initial structure, and constrained to their
+/// initial lengths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Shake {
+    /// No constraints
+    Off,
+    /// Constrain all bonds to hydrogen atoms
+    Hydrogen,
+    /// Constrain all bonds
+    All,
+    /// Constrain distances between explicit atom pairs (zero-based)
+    Bonds(Vec<(usize, usize)>),
+}
+
+/// State of dynamics after each step, passed to user callback.
+#[derive(Debug)]
+pub struct MdStep<'a> {
+    /// Current step number, 0 for the initial structure
+    pub step: usize,
+    /// Simulation time in fs
+    pub time: f64,
+    /// Kinetic energy in Hartree
+    pub kinetic_energy: f64,
+    /// Potential energy in Hartree
+    pub potential_energy: f64,
+    /// Total energy in Hartree
+    pub total_energy: f64,
+    /// Instantaneous temperature in K
+    pub temperature: f64,
+    /// Current positions in Bohr
+    pub positions: &'a [f64],
+    /// Current velocities in atomic units (Bohr / atomic time unit)
+    pub velocities: &'a [f64],
+}
+
+/// Final state of dynamics.
+#[derive(Clone, Debug)]
+pub struct MdReport {
+    /// Final positions in Bohr
+    pub positions: Vec<f64>,
+    /// Final velocities in atomic units
+    pub velocities: Vec<f64>,
+    /// Final potential energy in Hartree
+    pub potential_energy: f64,
+    /// Number of steps performed
+    pub n_steps: usize,
+    /// Average temperature over all steps in K
+    pub average_temperature: f64,
+}
+// f17b3c58 ends here
+
+// [[file:../xtb.note::63b8e2d1][63b8e2d1]]
+/// Born-Oppenheimer molecular dynamics with velocity Verlet integrator.
+#[derive(Clone, Debug)]
+pub struct Dynamics {
+    atom_types: Vec<i32>,
+    masses: Vec<f64>,
+    time_step: f64,
+    thermostat: Thermostat,
+    shake: Shake,
+    hydrogen_mass: Option<f64>,
+    initial_temperature: Option<f64>,
+    initial_velocities: Option<Vec<f64>>,
+    seed: Option<u64>,
+    log_file: Option<PathBuf>,
+    log_interval: usize,
+}
+
+impl Dynamics {
+    /// Create dynamics for atoms with atomic numbers in `atom_types`.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        let masses = atom_types
+            .iter()
+            .map(|&z| atomic_mass(z).ok_or_else(|| format_err!("no atomic mass for element {z}")))
+            .collect::<Result<_>>()?;
+        let md = Self {
+            atom_types: atom_types.to_vec(),
+            masses,
+            time_step: 0.5,
+            thermostat: Thermostat::Nve,
+            shake: Shake::Off,
+            hydrogen_mass: None,
+            initial_temperature: None,
+            initial_velocities: None,
+            seed: None,
+            log_file: None,
+            log_interval: 1,
+        };
+        Ok(md)
+    }
+
+    /// Set time step in fs. The default is 0.5 fs.
+    pub fn time_step(&mut self, dt: f64) -> &mut Self {
+        assert!(dt > 0.0, "invalid time step: {}", dt);
+        self.time_step = dt;
+        self
+    }
+
+    /// Set thermostat. The default is NVE without thermostat.
+    pub fn thermostat(&mut self, thermostat: Thermostat) -> &mut Self {
+        self.thermostat = thermostat;
+        self
+    }
+
+    /// Constrain bond lengths with SHAKE/RATTLE, which allows for longer
+    /// time steps, for example 2 fs with `Shake::Hydrogen`. The default is
+    /// `Shake::Off`.
+    pub fn shake(&mut self, shake: Shake) -> &mut Self {
+        self.shake = shake;
+        self
+    }
+
+    /// Repartition masses so that each hydrogen has `mass` in amu, with the
+    /// difference taken from its bonded heavy atom. Combined with SHAKE
+    /// this allows time steps up to 4 fs, similar to xtb.
+    pub fn hydrogen_mass(&mut self, mass: f64) -> &mut Self {
+        assert!(mass > 0.0, "invalid mass: {}", mass);
+        self.hydrogen_mass = Some(mass);
+        self
+    }
+
+    /// Override mass of atom `i` (zero-based) in amu.
+    pub fn mass(&mut self, i: usize, mass: f64) -> &mut Self {
+        assert!(mass > 0.0, "invalid mass: {}", mass);
+        self.masses[i] = mass;
+        self
+    }
+
+    /// Return masses of all atoms in amu, without hydrogen mass
+    /// repartitioning.
+    pub fn masses(&self) -> &[f64] {
+        &self.masses
+    }
+
+    /// Return masses in amu used for dynamics starting from `positions`,
+    /// including hydrogen mass repartitioning.
+    pub fn effective_masses(&self, positions: &[f64]) -> Result<Vec<f64>> {
+        let mut masses = self.masses.clone();
+        if let Some(mass) = self.hydrogen_mass {
+            for (i, j) in covalent_bonds(&self.atom_types, positions)? {
+                let (h, heavy) = match (self.atom_types[i], self.atom_types[j]) {
+                    (1, z) if z != 1 => (i, j),
+                    (z, 1) if z != 1 => (j, i),
+                    _ => continue,
+                };
+                // each hydrogen is repartitioned only once
+                if masses[h] == self.masses[h] {
+                    let delta = mass - masses[h];
+                    ensure!(masses[heavy] - delta > 0.0, "hydrogen mass {mass} is too large for atom {heavy}");
+                    masses[h] = mass;
+                    masses[heavy] -= delta;
+                }
+            }
+        }
+        Ok(masses)
+    }
+
+    /// Return constrained bonds with their lengths in the initial structure
+    /// at `positions`.
+    fn constrained_bonds(&self, positions: &[f64]) -> Result<Vec<(usize, usize, f64)>> {
+        let natoms = self.atom_types.len();
+        let bonds = match &self.shake {
+            Shake::Off => vec![],
+            Shake::All => covalent_bonds(&self.atom_types, positions)?,
+            Shake::Hydrogen => covalent_bonds(&self.atom_types, positions)?
+                .into_iter()
+                .filter(|&(i, j)| self.atom_types[i] == 1 || self.atom_types[j] == 1)
+                .collect(),
+            Shake::Bonds(bonds) => {
+                for &(i, j) in bonds {
+                    ensure!(i < natoms && j < natoms && i != j, "invalid bond ({i}, {j}) for {natoms} atoms");
+                }
+                bonds.clone()
+            }
+        };
+        let bonds = bonds
+            .into_iter()
+            .map(|(i, j)| (i, j, crate::internal::InternalCoordinate::Bond(i, j).value(positions)))
+            .collect();
+        Ok(bonds)
+    }
+
+    /// Set temperature in K for initial Maxwell-Boltzmann velocities. The
+    /// default is the thermostat temperature, or 298.15 K for NVE.
+    pub fn initial_temperature(&mut self, t: f64) -> &mut Self {
+        assert!(t >= 0.0, "invalid temperature: {}", t);
+        self.initial_temperature = Some(t);
+        self
+    }
+
+    /// Start from `velocities` in atomic units instead of random ones, for
+    /// example for continuing a previous run.
+    pub fn initial_velocities(&mut self, velocities: &[f64]) -> &mut Self {
+        self.initial_velocities = Some(velocities.to_vec());
+        self
+    }
+
+    /// Set seed of random number generator for reproducible runs.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Write energies and temperature every `interval` steps into `path`.
+    pub fn log_file(&mut self, path: impl AsRef<Path>, interval: usize) -> &mut Self {
+        assert!(interval > 0, "invalid log interval: {}", interval);
+        self.log_file = Some(path.as_ref().to_owned());
+        self.log_interval = interval;
+        self
+    }
+
+    /// Run dynamics on `pot` for `nsteps` starting from `positions` in Bohr.
+    pub fn run<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64], nsteps: usize) -> Result<MdReport> {
+        self.run_with(pot, positions, nsteps, |_| true)
+    }
+
+    /// Run dynamics on `pot` for `nsteps` starting from `positions` in Bohr.
+    /// `callback` is called for each step with current state, and the
+    /// dynamics will be stopped if it returns false.
+    pub fn run_with<P, F>(&self, pot: &mut P, positions: &[f64], nsteps: usize, mut callback: F) -> Result<MdReport>
+    where
+        P: Potential + ?Sized,
+        F: FnMut(&MdStep) -> bool,
+    {
+        let n = positions.len();
+        ensure!(n == 3 * self.masses.len(), "positions do not match {} atoms", self.masses.len());
+
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut log = match &self.log_file {
+            Some(path) => {
+                let f = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+                let mut f = std::io::BufWriter::new(f);
+                writeln!(f, "# step  time/fs  Ekin/Eh  Epot/Eh  Etot/Eh  T/K")?;
+                Some(f)
+            }
+            None => None,
+        };
+
+        // masses in atomic units for each coordinate
+        let masses = self.effective_masses(positions)?;
+        let m: Vec<_> = masses.iter().flat_map(|&m| [m * AMU_TO_AU; 3]).collect();
+        let rattle = Rattle {
+            bonds: self.constrained_bonds(positions)?,
+        };
+        let ndof = self.degrees_of_freedom().saturating_sub(rattle.bonds.len()).max(1);
+        let dt = self.time_step * FS_TO_AU;
+
+        let mut x = positions.to_vec();
+        let mut v = match &self.initial_velocities {
+            Some(v) => {
+                ensure!(v.len() == n, "invalid size of initial velocities: {}", v.len());
+                v.clone()
+            }
+            None => {
+                let t = self.initial_temperature.or_else(|| self.thermostat.temperature()).unwrap_or(298.15);
+                let mut v = maxwell_boltzmann(&m, t, &mut rng);
+                rattle.velocities(&x, &mut v, &m)?;
+                scale_to_temperature(&m, &mut v, t, ndof);
+                v
+            }
+        };
+        let mut g = vec![0.0; n];
+        let mut epot = pot.evaluate(&x, &mut g)?;
+
+        let mut temperature_sum = 0.0;
+        let mut step = 0;
+        loop {
+            let ekin = kinetic_energy(&m, &v);
+            let temperature = 2.0 * ekin / (ndof as f64 * KB);
+            temperature_sum += temperature;
+            let state = MdStep {
+                step,
+                time: step as f64 * self.time_step,
+                kinetic_energy: ekin,
+                potential_energy: epot,
+                total_energy: ekin + epot,
+                temperature,
+                positions: &x,
+                velocities: &v,
+            };
+            if let Some(f) = log.as_mut() {
+                if step % self.log_interval == 0 {
+                    writeln!(
+                        f,
+                        "{:8} {:12.3} {:16.8} {:16.8} {:16.8} {:10.2}",
+                        state.step, state.time, state.kinetic_energy, state.potential_energy, state.total_energy, state.temperature
+                    )?;
+                }
+            }
+            if !callback(&state) || step >= nsteps {
+                break;
+            }
+
+            step += 1;
+            if let Thermostat::Langevin { temperature, friction } = self.thermostat {
+                langevin_half_step(&m, &mut v, temperature, friction, self.time_step, &mut rng);
+                rattle.velocities(&x, &mut v, &m)?;
+            }
+            let mut x_new = x.clone();
+            for i in 0..n {
+                v[i] -= 0.5 * dt * g[i] / m[i];
+                x_new[i] += dt * v[i];
+            }
+            rattle.positions(&x, &mut x_new, &mut v, &m, dt)?;
+            x = x_new;
+            epot = pot.evaluate(&x, &mut g)?;
+            for i in 0..n {
+                v[i] -= 0.5 * dt * g[i] / m[i];
+            }
+            rattle.velocities(&x, &mut v, &m)?;
+            match self.thermostat {
+                Thermostat::Nve => {}
+                Thermostat::Berendsen { temperature, tau } => {
+                    let t = 2.0 * kinetic_energy(&m, &v) / (ndof as f64 * KB);
+                    if t > 0.0 {
+                        let lambda = (1.0 + self.time_step / tau * (temperature / t - 1.0)).max(0.0).sqrt();
+                        v.iter_mut().for_each(|x| *x *= lambda);
+                    }
+                }
+                Thermostat::Bussi { temperature, tau } => {
+                    let ekin = kinetic_energy(&m, &v);
+                    let alpha = bussi_scaling(ekin, temperature, tau / self.time_step, ndof, &mut rng);
+                    v.iter_mut().for_each(|x| *x *= alpha);
+                }
+                Thermostat::Langevin { temperature, friction } => {
+                    langevin_half_step(&m, &mut v, temperature, friction, self.time_step, &mut rng);
+                    rattle.velocities(&x, &mut v, &m)?;
+                }
+            }
+        }
+        if let Some(f) = log.as_mut() {
+            f.flush()?;
+        }
+
+        let report = MdReport {
+            positions: x,
+            velocities: v,
+            potential_energy: epot,
+            n_steps: step,
+            average_temperature: temperature_sum / (step + 1) as f64,
+        };
+        Ok(report)
+    }
+
+    // Langevin dynamics does not conserve the total momentum
+    fn degrees_of_freedom(&self) -> usize {
+        let n = 3 * self.masses.len();
+        match self.thermostat {
+            Thermostat::Langevin { .. } => n,
+            _ => n.saturating_sub(3).max(1),
+        }
+    }
+}
+
+fn kinetic_energy(m: &[f64], v: &[f64]) -> f64 {
+    0.5 * m.iter().zip(v).map(|(m, v)| m * v * v).sum::<f64>()
+}
+
+/// Random velocities from Maxwell-Boltzmann distribution at temperature
+/// `t`, with center of mass motion removed.
+fn maxwell_boltzmann(m: &[f64], t: f64, rng: &mut StdRng) -> Vec<f64> {
+    let mut v: Vec<f64> = m
+        .iter()
+        .map(|&m| {
+            let x: f64 = StandardNormal.sample(rng);
+            x * (KB * t / m).sqrt()
+        })
+        .collect();
+    let mtot: f64 = m.iter().step_by(3).sum();
+    for x in 0..3 {
+        let p: f64 = (x..v.len()).step_by(3).map(|i| m[i] * v[i]).sum();
+        for i in (x..v.len()).step_by(3) {
+            v[i] -= p / mtot;
+        }
+    }
+    v
+}
+
+/// Scale velocities `v` to temperature `t` exactly.
+fn scale_to_temperature(m: &[f64], v: &mut [f64], t: f64, ndof: usize) {
+    let ekin = kinetic_energy(m, v);
+    if ekin > 0.0 {
+        let scale = (0.5 * ndof as f64 * KB * t / ekin).sqrt();
+        v.iter_mut().for_each(|x| *x *= scale);
+    }
+}
+
+/// Scaling factor of velocities in the stochastic velocity rescaling
+/// thermostat, with coupling time `taut` in units of time steps.
+fn bussi_scaling(ekin: f64, t: f64, taut: f64, ndof: usize, rng: &mut StdRng) -> f64 {
+    if ekin <= 0.0 {
+        return 1.0;
+    }
+    let target = 0.5 * ndof as f64 * KB * t;
+    let c = (-1.0 / taut).exp();
+    let r1: f64 = StandardNormal.sample(rng);
+    let sum_noises = if ndof > 1 {
+        ChiSquared::new((ndof - 1) as f64).unwrap().sample(rng)
+    } else {
+        0.0
+    };
+    let ekin_new = ekin
+        + (1.0 - c) * (target * (sum_noises + r1 * r1) / ndof as f64 - ekin)
+        + 2.0 * r1 * (ekin * target / ndof as f64 * (1.0 - c) * c).sqrt();
+    (ekin_new.max(0.0) / ekin).sqrt()
+}
+
+/// Ornstein-Uhlenbeck update of velocities over half time step `dt` in fs.
+fn langevin_half_step(m: &[f64], v: &mut [f64], t: f64, friction: f64, dt: f64, rng: &mut StdRng) {
+    let c1 = (-0.5 * friction * dt).exp();
+    let c2 = (1.0 - c1 * c1).sqrt();
+    for (v, m) in v.iter_mut().zip(m) {
+        let r: f64 = StandardNormal.sample(rng);
+        *v = c1 * *v + c2 * (KB * t / m).sqrt() * r;
+    }
+}
+// 63b8e2d1 ends here
+
+// [[file:../xtb.note::a7c40e9b][a7c40e9b]]
+/// SHAKE/RATTLE constraints on bond lengths.
+struct Rattle {
+    bonds: Vec<(usize, usize, f64)>,
+}
+
+impl Rattle {
+    const TOLERANCE: f64 = 1e-10;
+    const MAX_ITERATIONS: usize = 1000;
+
+    /// Correct unconstrained positions `x_new` propagated from `x` by SHAKE,
+    /// together with velocities `v`.
+    fn positions(&self, x: &[f64], x_new: &mut [f64], v: &mut [f64], m: &[f64], dt: f64) -> Result<()> {
+        if self.bonds.is_empty() {
+            return Ok(());
+        }
+        for _ in 0..Self::MAX_ITERATIONS {
+            let mut converged = true;
+            for &(i, j, d) in self.bonds.iter() {
+                let r_new = [0, 1, 2].map(|k| x_new[3 * i + k] - x_new[3 * j + k]);
+                let diff = d * d - r_new.iter().map(|r| r * r).sum::<f64>();
+                if diff.abs() > 2.0 * Self::TOLERANCE * d * d {
+                    converged = false;
+                    let r_old = [0, 1, 2].map(|k| x[3 * i + k] - x[3 * j + k]);
+                    let (mi, mj) = (m[3 * i], m[3 * j]);
+                    let dot: f64 = r_old.iter().zip(&r_new).map(|(a, b)| a * b).sum();
+                    ensure!(dot.abs() > 1e-12, "SHAKE failed for bond ({i}, {j})");
+                    let g = diff / (2.0 * (1.0 / mi + 1.0 / mj) * dot);
+                    for k in 0..3 {
+                        x_new[3 * i + k] += g * r_old[k] / mi;
+                        x_new[3 * j + k] -= g * r_old[k] / mj;
+                        v[3 * i + k] += g * r_old[k] / (mi * dt);
+                        v[3 * j + k] -= g * r_old[k] / (mj * dt);
+                    }
+                }
+            }
+            if converged {
+                return Ok(());
+            }
+        }
+        bail!("SHAKE not converged in {} iterations", Self::MAX_ITERATIONS);
+    }
+
+    /// Remove velocity components along constrained bonds at positions `x`.
+    fn velocities(&self, x: &[f64], v: &mut [f64], m: &[f64]) -> Result<()> {
+        if self.bonds.is_empty() {
+            return Ok(());
+        }
+        for _ in 0..Self::MAX_ITERATIONS {
+            let mut converged = true;
+            for &(i, j, d) in self.bonds.iter() {
+                let r = [0, 1, 2].map(|k| x[3 * i + k] - x[3 * j + k]);
+                let rv: f64 = (0..3).map(|k| r[k] * (v[3 * i + k] - v[3 * j + k])).sum();
+                if rv.abs() > Self::TOLERANCE * d {
+                    converged = false;
+                    let (mi, mj) = (m[3 * i], m[3 * j]);
+                    let g = rv / (d * d * (1.0 / mi + 1.0 / mj));
+                    for k in 0..3 {
+                        v[3 * i + k] -= g * r[k] / mi;
+                        v[3 * j + k] += g * r[k] / mj;
+                    }
+                }
+            }
+            if converged {
+                return Ok(());
+            }
+        }
+        bail!("RATTLE not converged in {} iterations", Self::MAX_ITERATIONS);
+    }
+}
+// a7c40e9b ends here
@@ -0,0 +1,163 @@
+// [[file:../xtb.note::e94a2c17][e94a2c17]]
+//! RMSD-biased metadynamics for exploration of conformational space
+// e94a2c17 ends here
+
+// [[file:../xtb.note::30d8f6b5][30d8f6b5]]
+use crate::potential::Potential;
+use crate::rmsd::superimpose;
+
+use anyhow::*;
+// 30d8f6b5 ends here
+
+// [[file:../xtb.note::8c57e1a0][8c57e1a0]]
+/// Potential with a history-dependent bias pushing away from previously
+/// visited structures, in the spirit of the metadynamics of xtb (Grimme,
+/// J. Chem. Theory Comput. 15, 2847 (2019)). The bias energy is
+///
+///   E_bias = sum_i k exp(-alpha RMSD_i^2)
+///
+/// where RMSD_i is the root mean square deviation to the reference
+/// structure i. The current structure is deposited as a new reference every
+/// `interval` evaluations, that is every `interval` steps when the
+/// potential is used in `md::Dynamics`.
+pub struct Metadynamics<P> {
+    potential: P,
+    k: f64,
+    alpha: f64,
+    interval: usize,
+    max_references: Option<usize>,
+    references: Vec<Vec<f64>>,
+    n_evaluations: usize,
+    bias_energy: f64,
+}
+
+impl<P: Potential> Metadynamics<P> {
+    /// Apply RMSD bias on `potential`, without any reference structures.
+    pub fn new(potential: P) -> Self {
+        Self {
+            potential,
+            k: 0.02,
+            alpha: 1.0,
+            interval: 100,
+            max_references: None,
+            references: vec![],
+            n_evaluations: 0,
+            bias_energy: 0.0,
+        }
+    }
+
+    /// Set push strength `k` in Hartree for each reference structure.
+    /// Negative value pulls toward the references. The default is 0.02
+    /// Hartree.
+    pub fn push_strength(&mut self, k: f64) -> &mut Self {
+        self.k = k;
+        self
+    }
+
+    /// Set width parameter `alpha` of the Gaussians in Bohr^-2. The default
+    /// is 1.0 Bohr^-2.
+    pub fn width(&mut self, alpha: f64) -> &mut Self {
+        assert!(alpha > 0.0, "invalid width: {}", alpha);
+        self.alpha = alpha;
+        self
+    }
+
+    /// Deposit a reference structure every `n` evaluations. Zero disables
+    /// automatic deposition. The default is 100.
+    pub fn interval(&mut self, n: usize) -> &mut Self {
+        self.interval = n;
+        self
+    }
+
+    /// Keep at most `n` latest reference structures. By default all
+    /// structures are kept.
+    pub fn max_references(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of references: {}", n);
+        self.max_references = Some(n);
+        self.truncate();
+        self
+    }
+
+    /// Add reference structure with `positions` in Bohr, for example to
+    /// push away from known conformers.
+    pub fn add_reference(&mut self, positions: &[f64]) -> &mut Self {
+        self.references.push(positions.to_vec());
+        self.truncate();
+        self
+    }
+
+    /// Remove all reference structures and reset the counter of
+    /// evaluations.
+    pub fn clear(&mut self) {
+        self.references.clear();
+        self.n_evaluations = 0;
+    }
+
+    /// Return the reference structures in order of deposition.
+    pub fn references(&self) -> &[Vec<f64>] {
+        &self.references
+    }
+
+    /// Return bias energy in the last evaluation, which is included in the
+    /// total energy.
+    pub fn bias_energy(&self) -> f64 {
+        self.bias_energy
+    }
+
+    /// Return the wrapped potential.
+    pub fn inner(&mut self) -> &mut P {
+        &mut self.potential
+    }
+
+    /// Unwrap the potential.
+    pub fn into_inner(self) -> P {
+        self.potential
+    }
+
+    fn truncate(&mut self) {
+        if let Some(n) = self.max_references {
+            let m = self.references.len().saturating_sub(n);
+            self.references.drain(..m);
+        }
+    }
+
+    /// Add bias energy and gradient of all references at `positions`.
+    fn apply(&self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        let n = positions.len() as f64 / 3.0;
+        let mut energy = 0.0;
+        for reference in self.references.iter() {
+            ensure!(
+                reference.len() == positions.len(),
+                "reference structure does not match {} atoms",
+                positions.len() / 3
+            );
+            // the optimal rotation is stationary, so the gradient of squared
+            // RMSD is simply the difference to the superimposed reference
+            let aligned = superimpose(positions, reference);
+            let msd = positions.iter().zip(&aligned).map(|(x, y)| (x - y).powi(2)).sum::<f64>() / n;
+            let e = self.k * (-self.alpha * msd).exp();
+            energy += e;
+            for ((g, x), y) in gradient.iter_mut().zip(positions).zip(&aligned) {
+                *g -= e * self.alpha * 2.0 * (x - y) / n;
+            }
+        }
+        Ok(energy)
+    }
+}
+
+impl<P: Potential> Potential for Metadynamics<P> {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        let energy = self.potential.evaluate(positions, gradient)?;
+        self.bias_energy = self.apply(positions, gradient)?;
+        self.n_evaluations += 1;
+        if self.interval > 0 && self.n_evaluations % self.interval == 0 {
+            self.add_reference(positions);
+        }
+        Ok(energy + self.bias_energy)
+    }
+
+    fn frozen_atoms(&self) -> &[usize] {
+        self.potential.frozen_atoms()
+    }
+}
+// 8c57e1a0 ends here
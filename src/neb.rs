@@ -0,0 +1,456 @@
+// [[file:../xtb.note::a3f1c8d4][a3f1c8d4]]
+//! Minimum energy path with climbing image nudged elastic band (CI-NEB)
+// a3f1c8d4 ends here
+
+// [[file:../xtb.note::5e07b2a9][5e07b2a9]]
+use crate::opt::{limit_step, Fire, Lbfgs, OptTermination, Stepper};
+use crate::potential::Potential;
+use crate::rmsd::superimpose;
+use crate::utils::*;
+
+use anyhow::*;
+use std::io::Write;
+use std::path::Path;
+// 5e07b2a9 ends here
+
+// [[file:../xtb.note::c81d4e63][c81d4e63]]
+/// Interpolation of initial images between endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Linear interpolation in Cartesian coordinates
+    Linear,
+    /// Image dependent pair potential of Smidstrup et al. (J. Chem. Phys.
+    /// 140, 214106 (2014)), which avoids atoms running into each other
+    Idpp,
+}
+
+/// Optimizer for relaxation of images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NebOptimizer {
+    Fire,
+    Lbfgs,
+}
+
+/// Climbing image nudged elastic band method with the improved tangent of
+/// Henkelman and Jónsson (J. Chem. Phys. 113, 9978 (2000)). The climbing
+/// image is switched on after the path is roughly converged.
+#[derive(Clone, Debug)]
+pub struct Neb {
+    nimages: usize,
+    spring: f64,
+    climbing: bool,
+    interpolation: Interpolation,
+    optimizer: NebOptimizer,
+    align: bool,
+    max_force: f64,
+    max_steps: usize,
+    max_step: f64,
+}
+
+impl Default for Neb {
+    fn default() -> Self {
+        Self {
+            nimages: 8,
+            spring: 0.01,
+            climbing: true,
+            interpolation: Interpolation::Idpp,
+            optimizer: NebOptimizer::Fire,
+            align: true,
+            max_force: 1e-3,
+            max_steps: 500,
+            max_step: 0.2,
+        }
+    }
+}
+
+/// Progress of NEB optimization after each step, passed to user callback.
+#[derive(Debug)]
+pub struct NebProgress<'a> {
+    /// Current step number, 0 for the initial path
+    pub step: usize,
+    /// Largest atomic NEB force of all images in Hartree / Bohr
+    pub max_force: f64,
+    /// Index of the climbing image if switched on
+    pub climbing_image: Option<usize>,
+    /// Energies of all images including endpoints in Hartree
+    pub energies: &'a [f64],
+}
+
+/// Final report of NEB calculation.
+#[derive(Clone, Debug)]
+pub struct NebReport {
+    /// Positions of all images including endpoints in Bohr
+    pub images: Vec<Vec<f64>>,
+    /// Energies of all images including endpoints in Hartree
+    pub energies: Vec<f64>,
+    /// Number of optimization steps taken
+    pub n_steps: usize,
+    /// Index of the climbing image if switched on
+    pub climbing_image: Option<usize>,
+    /// Reason of termination
+    pub termination: OptTermination,
+}
+
+impl NebReport {
+    /// Return true if the path converged.
+    pub fn converged(&self) -> bool {
+        self.termination == OptTermination::Converged
+    }
+
+    /// Return index of the intermediate image with the highest energy.
+    pub fn highest_image(&self) -> usize {
+        let n = self.energies.len();
+        (1..n - 1)
+            .max_by(|&a, &b| self.energies[a].total_cmp(&self.energies[b]))
+            .expect("no intermediate images")
+    }
+
+    /// Return positions of the highest energy image as a guess of the
+    /// transition state.
+    pub fn ts_guess(&self) -> &[f64] {
+        &self.images[self.highest_image()]
+    }
+
+    /// Return forward barrier in Hartree.
+    pub fn barrier(&self) -> f64 {
+        self.energies[self.highest_image()] - self.energies[0]
+    }
+
+    /// Return reaction energy in Hartree.
+    pub fn reaction_energy(&self) -> f64 {
+        self.energies[self.energies.len() - 1] - self.energies[0]
+    }
+
+    /// Return energy profile as (path length in Bohr, energy in Hartree
+    /// relative to the first image) for each image.
+    pub fn profile(&self) -> Vec<[f64; 2]> {
+        let mut s = 0.0;
+        let e0 = self.energies[0];
+        self.images
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                if i > 0 {
+                    let d: Vec<_> = x.iter().zip(&self.images[i - 1]).map(|(a, b)| a - b).collect();
+                    s += norm(&d);
+                }
+                [s, self.energies[i] - e0]
+            })
+            .collect()
+    }
+
+    /// Write energy profile into `path` as columns of image index, path
+    /// length in Bohr, and relative energy in Hartree and kcal/mol.
+    pub fn write_profile(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let f = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut f = std::io::BufWriter::new(f);
+        writeln!(f, "# image  path/Bohr  dE/Eh  dE/(kcal/mol)")?;
+        for (i, [s, e]) in self.profile().into_iter().enumerate() {
+            writeln!(f, "{:5} {:12.6} {:16.10} {:12.4}", i, s, e, e * 627.5095)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+}
+
+impl Neb {
+    /// Set the number of intermediate images. The default is 8.
+    pub fn images(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of images: {}", n);
+        self.nimages = n;
+        self
+    }
+
+    /// Set spring constant between images in Hartree / Bohr^2. The default
+    /// is 0.01.
+    pub fn spring_constant(&mut self, k: f64) -> &mut Self {
+        assert!(k > 0.0, "invalid spring constant: {}", k);
+        self.spring = k;
+        self
+    }
+
+    /// Enable or disable climbing image. The default is enabled.
+    pub fn climbing(&mut self, climbing: bool) -> &mut Self {
+        self.climbing = climbing;
+        self
+    }
+
+    /// Set interpolation of initial images. The default is IDPP.
+    pub fn interpolation(&mut self, interpolation: Interpolation) -> &mut Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Set optimizer for relaxation of images. The default is FIRE.
+    pub fn optimizer(&mut self, optimizer: NebOptimizer) -> &mut Self {
+        self.optimizer = optimizer;
+        self
+    }
+
+    /// Enable or disable superposition of the final structure onto the
+    /// initial one before interpolation. The default is enabled.
+    pub fn align(&mut self, align: bool) -> &mut Self {
+        self.align = align;
+        self
+    }
+
+    /// Set convergence threshold of the largest atomic NEB force in
+    /// Hartree / Bohr. The default is 1e-3.
+    pub fn max_force(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid force threshold: {}", x);
+        self.max_force = x;
+        self
+    }
+
+    /// Set maximum number of optimization steps. The default is 500.
+    pub fn max_steps(&mut self, n: usize) -> &mut Self {
+        self.max_steps = n;
+        self
+    }
+
+    /// Set maximum displacement of any atom in a single step in Bohr. The
+    /// default is 0.2 Bohr.
+    pub fn max_step_size(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid step size: {}", x);
+        self.max_step = x;
+        self
+    }
+
+    /// Return initial path between `start` and `end` in Bohr including
+    /// both endpoints.
+    pub fn interpolate(&self, start: &[f64], end: &[f64]) -> Result<Vec<Vec<f64>>> {
+        let n = start.len();
+        ensure!(
+            n > 0 && n % 3 == 0 && end.len() == n,
+            "invalid size of endpoints: {} and {}",
+            n,
+            end.len()
+        );
+        let end = if self.align { superimpose(start, end) } else { end.to_vec() };
+        let m = self.nimages + 1;
+        let mut path: Vec<Vec<f64>> = (0..=m)
+            .map(|i| {
+                let t = i as f64 / m as f64;
+                start.iter().zip(&end).map(|(a, b)| a + t * (b - a)).collect()
+            })
+            .collect();
+
+        if self.interpolation == Interpolation::Idpp {
+            let d0 = pair_distances(start);
+            let d1 = pair_distances(&end);
+            let mut pots: Vec<_> = (0..=m)
+                .map(|i| {
+                    let t = i as f64 / m as f64;
+                    let target = d0.iter().zip(&d1).map(|(a, b)| a + t * (b - a)).collect();
+                    Idpp { target }
+                })
+                .collect();
+            let mut energies = vec![0.0; m + 1];
+            let mut neb = self.clone();
+            neb.climbing(false).max_force(1e-2).max_steps(self.max_steps.min(200));
+            neb.relax(&mut pots, &mut path, &mut energies, |_| true)?;
+        }
+        Ok(path)
+    }
+
+    /// Compute minimum energy path between `start` and `end` in Bohr. Each
+    /// image is evaluated with its own potential created by `create` from
+    /// its initial positions, for example an `XtbModel`, so that the
+    /// states like SCC guesses are kept per image.
+    pub fn run<P, F>(&self, create: F, start: &[f64], end: &[f64]) -> Result<NebReport>
+    where
+        P: Potential,
+        F: FnMut(&[f64]) -> Result<P>,
+    {
+        self.run_with(create, start, end, |_| true)
+    }
+
+    /// Compute minimum energy path between `start` and `end` in Bohr.
+    /// `callback` is called for each step with current progress, and the
+    /// optimization will be stopped if it returns false.
+    pub fn run_with<P, F, C>(&self, mut create: F, start: &[f64], end: &[f64], callback: C) -> Result<NebReport>
+    where
+        P: Potential,
+        F: FnMut(&[f64]) -> Result<P>,
+        C: FnMut(&NebProgress) -> bool,
+    {
+        let mut path = self.interpolate(start, end)?;
+        let mut pots = path.iter().map(|x| create(x)).collect::<Result<Vec<_>>>()?;
+        let mut energies = vec![0.0; path.len()];
+        let m = path.len() - 1;
+        for i in [0, m] {
+            let mut g = vec![0.0; path[i].len()];
+            energies[i] = pots[i].evaluate(&path[i], &mut g)?;
+        }
+        let (n_steps, climbing_image, termination) = self.relax(&mut pots, &mut path, &mut energies, callback)?;
+
+        let report = NebReport {
+            images: path,
+            energies,
+            n_steps,
+            climbing_image,
+            termination,
+        };
+        Ok(report)
+    }
+
+    /// Relax intermediate images of `path` on `pots`, with energies of
+    /// endpoints given in `energies`.
+    fn relax<P, C>(
+        &self,
+        pots: &mut [P],
+        path: &mut [Vec<f64>],
+        energies: &mut [f64],
+        mut callback: C,
+    ) -> Result<(usize, Option<usize>, OptTermination)>
+    where
+        P: Potential,
+        C: FnMut(&NebProgress) -> bool,
+    {
+        let m = path.len() - 1;
+        let n = path[0].len();
+        let new_stepper = || -> Box<dyn Stepper> {
+            match self.optimizer {
+                NebOptimizer::Fire => Box::new(Fire::default().stepper()),
+                NebOptimizer::Lbfgs => Box::new(Lbfgs::default().stepper()),
+            }
+        };
+        let mut stepper = new_stepper();
+
+        let mut gradients = vec![vec![0.0; n]; m + 1];
+        for i in 1..m {
+            energies[i] = pots[i].evaluate(&path[i], &mut gradients[i])?;
+        }
+        let mut x: Vec<_> = path[1..m].concat();
+        let mut climbing_image = None;
+        let mut last: Option<(Vec<f64>, Vec<f64>)> = None;
+        let mut step = 0;
+        let termination = loop {
+            if climbing_image.is_some() {
+                climbing_image = (1..m).max_by(|&a, &b| energies[a].total_cmp(&energies[b]));
+            }
+            let mut g = self.neb_gradient(path, energies, &gradients, climbing_image);
+            let mut max_force = max_atom_norm(&g);
+            if self.climbing && climbing_image.is_none() && max_force < 10.0 * self.max_force {
+                // switch on climbing image with new optimizer state
+                climbing_image = (1..m).max_by(|&a, &b| energies[a].total_cmp(&energies[b]));
+                g = self.neb_gradient(path, energies, &gradients, climbing_image);
+                max_force = max_atom_norm(&g);
+                stepper = new_stepper();
+                last = None;
+            }
+            let progress = NebProgress {
+                step,
+                max_force,
+                climbing_image,
+                energies,
+            };
+            if !callback(&progress) {
+                break OptTermination::Stopped;
+            }
+            if max_force < self.max_force {
+                break OptTermination::Converged;
+            }
+            if step >= self.max_steps {
+                break OptTermination::MaxStepsReached;
+            }
+
+            if let Some((s, g_last)) = last.take() {
+                let y: Vec<_> = g.iter().zip(&g_last).map(|(a, b)| a - b).collect();
+                stepper.update(&s, &y);
+            }
+            step += 1;
+            let mut d = stepper.propose(&x, &g, self.max_step)?;
+            limit_step(&mut d, self.max_step);
+            x.iter_mut().zip(&d).for_each(|(xi, di)| *xi += di);
+            for i in 1..m {
+                path[i].copy_from_slice(&x[(i - 1) * n..i * n]);
+                energies[i] = pots[i].evaluate(&path[i], &mut gradients[i])?;
+            }
+            last = Some((d, g));
+        };
+        Ok((step, climbing_image, termination))
+    }
+
+    /// Gradient of intermediate images with NEB projections, which is the
+    /// negative NEB force.
+    fn neb_gradient(&self, path: &[Vec<f64>], energies: &[f64], gradients: &[Vec<f64>], climbing_image: Option<usize>) -> Vec<f64> {
+        let m = path.len() - 1;
+        let mut neb_gradient = vec![];
+        for i in 1..m {
+            let tau_p: Vec<_> = path[i + 1].iter().zip(&path[i]).map(|(a, b)| a - b).collect();
+            let tau_m: Vec<_> = path[i].iter().zip(&path[i - 1]).map(|(a, b)| a - b).collect();
+            let (e_p, e, e_m) = (energies[i + 1], energies[i], energies[i - 1]);
+            // improved tangent from the higher energy neighbor
+            let mut tau: Vec<_> = if e_p > e && e > e_m {
+                tau_p.clone()
+            } else if e_p < e && e < e_m {
+                tau_m.clone()
+            } else {
+                let dv_max = (e_p - e).abs().max((e_m - e).abs());
+                let dv_min = (e_p - e).abs().min((e_m - e).abs());
+                let (wp, wm) = if e_p > e_m { (dv_max, dv_min) } else { (dv_min, dv_max) };
+                tau_p.iter().zip(&tau_m).map(|(p, m)| wp * p + wm * m).collect()
+            };
+            let tnorm = norm(&tau);
+            if tnorm > 0.0 {
+                tau.iter_mut().for_each(|t| *t /= tnorm);
+            }
+
+            let g = &gradients[i];
+            let gt = dot(g, &tau);
+            if climbing_image == Some(i) {
+                neb_gradient.extend(g.iter().zip(&tau).map(|(g, t)| g - 2.0 * gt * t));
+            } else {
+                let spring = self.spring * (norm(&tau_p) - norm(&tau_m));
+                neb_gradient.extend(g.iter().zip(&tau).map(|(g, t)| g - gt * t - spring * t));
+            }
+        }
+        neb_gradient
+    }
+}
+
+/// Distances between all pairs of atoms.
+fn pair_distances(positions: &[f64]) -> Vec<f64> {
+    let atoms: Vec<_> = positions.chunks_exact(3).collect();
+    let mut d = vec![];
+    for i in 0..atoms.len() {
+        for j in 0..i {
+            let r: Vec<_> = atoms[i].iter().zip(atoms[j]).map(|(a, b)| a - b).collect();
+            d.push(norm(&r));
+        }
+    }
+    d
+}
+
+/// Image dependent pair potential with target pair distances.
+struct Idpp {
+    target: Vec<f64>,
+}
+
+impl Potential for Idpp {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        gradient.fill(0.0);
+        let mut energy = 0.0;
+        let mut k = 0;
+        for i in 0..positions.len() / 3 {
+            for j in 0..i {
+                let r: Vec<_> = (0..3).map(|x| positions[3 * i + x] - positions[3 * j + x]).collect();
+                let d = norm(&r);
+                let dd = d - self.target[k];
+                k += 1;
+                // weighted by 1/d^4
+                let w = d.powi(-4);
+                energy += w * dd * dd;
+                let de = 2.0 * w * dd - 4.0 * w / d * dd * dd;
+                for x in 0..3 {
+                    gradient[3 * i + x] += de * r[x] / d;
+                    gradient[3 * j + x] -= de * r[x] / d;
+                }
+            }
+        }
+        Ok(energy)
+    }
+}
+// c81d4e63 ends here
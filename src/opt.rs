@@ -0,0 +1,760 @@
+// [[file:../xtb.note::5d2a8f17][5d2a8f17]]
+//! Geometry optimization with L-BFGS and FIRE algorithms in Cartesian
+//! coordinates, and rational function optimization in redundant internal
+//! coordinates
+// 5d2a8f17 ends here
+
+// [[file:../xtb.note::c9e03b45][c9e03b45]]
+use super::*;
+use crate::utils::*;
+
+use crate::internal::RedundantInternals;
+
+use nalgebra::{DMatrix, DVector};
+use std::collections::VecDeque;
+// c9e03b45 ends here
+
+// [[file:../xtb.note::3a7de6f0][3a7de6f0]]
+/// Convergence criteria for geometry optimization in atomic units. The
+/// default values follow the "normal" level of xtb.
+#[derive(Clone, Debug)]
+pub struct OptConvergence {
+    energy: f64,
+    max_gradient: f64,
+    rms_gradient: f64,
+    max_displacement: f64,
+}
+
+impl Default for OptConvergence {
+    fn default() -> Self {
+        Self::normal()
+    }
+}
+
+impl OptConvergence {
+    /// Loose convergence criteria, similar to `--opt loose` in xtb.
+    pub fn loose() -> Self {
+        Self {
+            energy: 5e-5,
+            max_gradient: 4e-3,
+            rms_gradient: 2e-3,
+            max_displacement: 1e-2,
+        }
+    }
+
+    /// Normal convergence criteria, similar to `--opt normal` in xtb.
+    pub fn normal() -> Self {
+        Self {
+            energy: 5e-6,
+            max_gradient: 1e-3,
+            rms_gradient: 5e-4,
+            max_displacement: 4e-3,
+        }
+    }
+
+    /// Tight convergence criteria, similar to `--opt tight` in xtb.
+    pub fn tight() -> Self {
+        Self {
+            energy: 1e-6,
+            max_gradient: 8e-4,
+            rms_gradient: 4e-4,
+            max_displacement: 2e-3,
+        }
+    }
+
+    /// Set threshold of energy change between steps in Hartree.
+    pub fn energy(&mut self, x: f64) -> &mut Self {
+        self.energy = x;
+        self
+    }
+
+    /// Set threshold of the largest gradient component in Hartree / Bohr.
+    pub fn max_gradient(&mut self, x: f64) -> &mut Self {
+        self.max_gradient = x;
+        self
+    }
+
+    /// Set threshold of RMS gradient in Hartree / Bohr.
+    pub fn rms_gradient(&mut self, x: f64) -> &mut Self {
+        self.rms_gradient = x;
+        self
+    }
+
+    /// Set threshold of the largest atomic displacement between steps in Bohr.
+    pub fn max_displacement(&mut self, x: f64) -> &mut Self {
+        self.max_displacement = x;
+        self
+    }
+
+    /// Check if `progress` satisfies all the criteria.
+    pub(crate) fn is_converged(&self, progress: &OptProgress) -> bool {
+        let gradient_ok = progress.max_gradient <= self.max_gradient && progress.rms_gradient <= self.rms_gradient;
+        // the initial structure is accepted based on gradient only
+        if progress.step == 0 {
+            return gradient_ok;
+        }
+        gradient_ok
+            && progress.energy_change.abs() <= self.energy
+            && progress.max_displacement <= self.max_displacement
+    }
+}
+// 3a7de6f0 ends here
+
+// [[file:../xtb.note::e2b71c98][e2b71c98]]
+/// Reason why the optimization terminated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptTermination {
+    /// All convergence criteria satisfied
+    Converged,
+    /// Maximum number of steps reached before convergence
+    MaxStepsReached,
+    /// Stopped by user callback
+    Stopped,
+}
+
+/// Progress of optimization after each step, passed to user callback.
+#[derive(Debug)]
+pub struct OptProgress<'a> {
+    /// Current step number, 0 for the initial structure
+    pub step: usize,
+    /// Number of energy and gradient evaluations so far
+    pub n_evaluations: usize,
+    /// Current energy in Hartree
+    pub energy: f64,
+    /// Energy change from previous step in Hartree
+    pub energy_change: f64,
+    /// Largest gradient component in Hartree / Bohr
+    pub max_gradient: f64,
+    /// RMS gradient in Hartree / Bohr
+    pub rms_gradient: f64,
+    /// Largest atomic displacement from previous step in Bohr
+    pub max_displacement: f64,
+    /// Current positions in Bohr
+    pub positions: &'a [f64],
+    /// Current gradient in Hartree / Bohr
+    pub gradient: &'a [f64],
+}
+
+/// Final report of geometry optimization.
+#[derive(Clone, Debug)]
+pub struct OptReport {
+    /// Final energy in Hartree
+    pub energy: f64,
+    /// Final positions in Bohr
+    pub positions: Vec<f64>,
+    /// Final gradient in Hartree / Bohr
+    pub gradient: Vec<f64>,
+    /// Number of optimization steps taken
+    pub n_steps: usize,
+    /// Number of energy and gradient evaluations
+    pub n_evaluations: usize,
+    /// Reason of termination
+    pub termination: OptTermination,
+}
+
+impl OptReport {
+    /// Return true if the optimization converged.
+    pub fn converged(&self) -> bool {
+        self.termination == OptTermination::Converged
+    }
+}
+// e2b71c98 ends here
+
+// [[file:../xtb.note::1b5f0d7c][1b5f0d7c]]
+/// Core of an optimization algorithm, which proposes displacement from
+/// current gradient.
+pub(crate) trait Stepper {
+    /// Prepare for optimization starting from `positions` with `frozen`
+    /// atoms of the potential.
+    fn init(&mut self, _positions: &[f64], _frozen: &[usize]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Propose displacement from current `positions` and `gradient`. The
+    /// displacement will be scaled down if any atom moves beyond `max_step`.
+    fn propose(&mut self, positions: &[f64], gradient: &[f64], max_step: f64) -> Result<Vec<f64>>;
+
+    /// Decide if the proposed step should be accepted from energy change.
+    fn accept(&mut self, energy_change: f64) -> bool;
+
+    /// Update internal state after an accepted step `s` with gradient
+    /// change `y`.
+    fn update(&mut self, s: &[f64], y: &[f64]);
+}
+
+/// Scale down displacement `d` so that no atom moves beyond `max_step`.
+pub(crate) fn limit_step(d: &mut [f64], max_step: f64) {
+    let dmax = max_atom_norm(d);
+    if dmax > max_step {
+        let scale = max_step / dmax;
+        d.iter_mut().for_each(|x| *x *= scale);
+    }
+}
+
+/// Common settings of optimization driver.
+#[derive(Clone, Debug)]
+struct OptDriver {
+    convergence: OptConvergence,
+    max_steps: usize,
+    max_step: f64,
+}
+
+impl Default for OptDriver {
+    fn default() -> Self {
+        Self {
+            convergence: OptConvergence::default(),
+            max_steps: 500,
+            max_step: 0.3,
+        }
+    }
+}
+
+impl OptDriver {
+    fn run<P, S, F>(&self, pot: &mut P, positions: &[f64], stepper: &mut S, mut callback: F) -> Result<OptReport>
+    where
+        P: Potential + ?Sized,
+        S: Stepper,
+        F: FnMut(&OptProgress) -> bool,
+    {
+        let n = positions.len();
+        ensure!(
+            n > 0 && positions.chunks_exact(3).remainder().is_empty(),
+            "invalid size of positions: {}",
+            n
+        );
+
+        stepper.init(positions, pot.frozen_atoms())?;
+
+        let mut x = positions.to_vec();
+        let mut g = vec![0.0; n];
+        let mut e = pot.evaluate(&x, &mut g)?;
+        let mut n_evaluations = 1;
+
+        let mut x_new = vec![0.0; n];
+        let mut g_new = vec![0.0; n];
+        let mut step = 0;
+        let mut energy_change = 0.0;
+        let mut max_displacement = 0.0;
+        let mut rejected = false;
+        let termination = loop {
+            let progress = OptProgress {
+                step,
+                n_evaluations,
+                energy: e,
+                energy_change,
+                max_gradient: max_abs(&g),
+                rms_gradient: rms(&g),
+                max_displacement,
+                positions: &x,
+                gradient: &g,
+            };
+            if !callback(&progress) {
+                break OptTermination::Stopped;
+            }
+            if !rejected && self.convergence.is_converged(&progress) {
+                break OptTermination::Converged;
+            }
+            if step >= self.max_steps {
+                break OptTermination::MaxStepsReached;
+            }
+
+            step += 1;
+            let mut d = stepper.propose(&x, &g, self.max_step)?;
+            limit_step(&mut d, self.max_step);
+            for i in 0..n {
+                x_new[i] = x[i] + d[i];
+            }
+            let e_new = pot.evaluate(&x_new, &mut g_new)?;
+            n_evaluations += 1;
+            rejected = !stepper.accept(e_new - e);
+            if !rejected {
+                let y: Vec<_> = g_new.iter().zip(&g).map(|(a, b)| a - b).collect();
+                stepper.update(&d, &y);
+                energy_change = e_new - e;
+                max_displacement = max_atom_norm(&d);
+                e = e_new;
+                x.clone_from(&x_new);
+                g.clone_from(&g_new);
+            }
+        };
+
+        let report = OptReport {
+            energy: e,
+            positions: x,
+            gradient: g,
+            n_steps: step,
+            n_evaluations,
+            termination,
+        };
+        Ok(report)
+    }
+}
+
+macro_rules! impl_opt_driver_settings {
+    ($opt:ident) => {
+        impl $opt {
+            /// Set convergence criteria.
+            pub fn convergence(&mut self, convergence: OptConvergence) -> &mut Self {
+                self.driver.convergence = convergence;
+                self
+            }
+
+            /// Set maximum number of optimization steps.
+            pub fn max_steps(&mut self, n: usize) -> &mut Self {
+                self.driver.max_steps = n;
+                self
+            }
+
+            /// Set maximum displacement of any atom in a single step in Bohr.
+            pub fn max_step_size(&mut self, x: f64) -> &mut Self {
+                assert!(x > 0.0, "invalid step size: {}", x);
+                self.driver.max_step = x;
+                self
+            }
+
+            /// Minimize energy of `pot` starting from `positions` in Bohr.
+            pub fn minimize<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<OptReport> {
+                self.minimize_with(pot, positions, |_| true)
+            }
+
+            /// Minimize energy of `pot` starting from `positions` in Bohr.
+            /// `callback` is called for each step with current progress,
+            /// and the optimization will be stopped if it returns false.
+            pub fn minimize_with<P, F>(&self, pot: &mut P, positions: &[f64], callback: F) -> Result<OptReport>
+            where
+                P: Potential + ?Sized,
+                F: FnMut(&OptProgress) -> bool,
+            {
+                let mut stepper = self.stepper();
+                self.driver.run(pot, positions, &mut stepper, callback)
+            }
+        }
+    };
+}
+// 1b5f0d7c ends here
+
+// [[file:../xtb.note::8d4e9a02][8d4e9a02]]
+/// Limited-memory BFGS optimizer with trust radius step control.
+#[derive(Clone, Debug)]
+pub struct Lbfgs {
+    driver: OptDriver,
+    memory: usize,
+    initial_curvature: f64,
+}
+
+impl Default for Lbfgs {
+    fn default() -> Self {
+        Self {
+            driver: OptDriver::default(),
+            memory: 20,
+            initial_curvature: 0.5,
+        }
+    }
+}
+
+impl_opt_driver_settings!(Lbfgs);
+
+impl Lbfgs {
+    /// Set the number of previous steps used to approximate the inverse
+    /// Hessian.
+    pub fn memory(&mut self, n: usize) -> &mut Self {
+        self.memory = n;
+        self
+    }
+
+    /// Set curvature of the initial Hessian guess in Hartree / Bohr^2.
+    pub fn initial_curvature(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid curvature: {}", x);
+        self.initial_curvature = x;
+        self
+    }
+
+    pub(crate) fn stepper(&self) -> LbfgsStepper {
+        LbfgsStepper {
+            memory: self.memory,
+            initial_curvature: self.initial_curvature,
+            history: VecDeque::new(),
+            trust: self.driver.max_step,
+        }
+    }
+}
+
+pub(crate) struct LbfgsStepper {
+    memory: usize,
+    initial_curvature: f64,
+    // (s, y, rho) of previous steps
+    history: VecDeque<(Vec<f64>, Vec<f64>, f64)>,
+    // adaptive trust radius
+    trust: f64,
+}
+
+impl Stepper for LbfgsStepper {
+    fn propose(&mut self, _positions: &[f64], gradient: &[f64], max_step: f64) -> Result<Vec<f64>> {
+        self.trust = self.trust.min(max_step);
+        // two-loop recursion
+        let mut q = gradient.to_vec();
+        let mut alpha = vec![0.0; self.history.len()];
+        for (k, (s, y, rho)) in self.history.iter().enumerate().rev() {
+            alpha[k] = rho * dot(s, &q);
+            q.iter_mut().zip(y).for_each(|(qi, yi)| *qi -= alpha[k] * yi);
+        }
+        let gamma = match self.history.back() {
+            Some((s, y, _)) => dot(s, y) / dot(y, y),
+            None => 1.0 / self.initial_curvature,
+        };
+        q.iter_mut().for_each(|x| *x *= gamma);
+        for (k, (s, y, rho)) in self.history.iter().enumerate() {
+            let beta = rho * dot(y, &q);
+            q.iter_mut().zip(s).for_each(|(qi, si)| *qi += (alpha[k] - beta) * si);
+        }
+        // make sure it is a descent direction
+        if dot(&q, gradient) <= 0.0 {
+            self.history.clear();
+            q = gradient.iter().map(|x| x / self.initial_curvature).collect();
+        }
+        let mut d: Vec<_> = q.into_iter().map(|x| -x).collect();
+        limit_step(&mut d, self.trust);
+        Ok(d)
+    }
+
+    fn accept(&mut self, energy_change: f64) -> bool {
+        if energy_change > 1e-6 {
+            // uphill step: restart from steepest descent with smaller trust radius
+            self.history.clear();
+            self.trust *= 0.5;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn update(&mut self, s: &[f64], y: &[f64]) {
+        let sy = dot(s, y);
+        // skip update if the curvature condition fails
+        if sy > 1e-10 {
+            if self.history.len() >= self.memory {
+                self.history.pop_front();
+            }
+            self.history.push_back((s.to_vec(), y.to_vec(), 1.0 / sy));
+        }
+        // recover trust radius after successful steps
+        self.trust *= 1.5;
+    }
+}
+// 8d4e9a02 ends here
+
+// [[file:../xtb.note::f6c13b7a][f6c13b7a]]
+/// Fast inertial relaxation engine (FIRE) optimizer, with unit masses and
+/// time step in atomic units.
+#[derive(Clone, Debug)]
+pub struct Fire {
+    driver: OptDriver,
+    dt: f64,
+    dt_max: f64,
+    n_min: usize,
+    f_inc: f64,
+    f_dec: f64,
+    alpha: f64,
+    f_alpha: f64,
+}
+
+impl Default for Fire {
+    fn default() -> Self {
+        Self {
+            driver: OptDriver::default(),
+            dt: 1.0,
+            dt_max: 10.0,
+            n_min: 5,
+            f_inc: 1.1,
+            f_dec: 0.5,
+            alpha: 0.1,
+            f_alpha: 0.99,
+        }
+    }
+}
+
+impl_opt_driver_settings!(Fire);
+
+impl Fire {
+    /// Set initial time step.
+    pub fn time_step(&mut self, dt: f64) -> &mut Self {
+        assert!(dt > 0.0, "invalid time step: {}", dt);
+        self.dt = dt;
+        self
+    }
+
+    /// Set maximum time step.
+    pub fn max_time_step(&mut self, dt: f64) -> &mut Self {
+        assert!(dt > 0.0, "invalid time step: {}", dt);
+        self.dt_max = dt;
+        self
+    }
+
+    /// Set initial mixing parameter between velocity and force.
+    pub fn alpha(&mut self, alpha: f64) -> &mut Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub(crate) fn stepper(&self) -> FireStepper {
+        FireStepper {
+            params: self.clone(),
+            velocity: vec![],
+            dt: self.dt,
+            alpha: self.alpha,
+            n_positive: 0,
+        }
+    }
+}
+
+pub(crate) struct FireStepper {
+    params: Fire,
+    velocity: Vec<f64>,
+    dt: f64,
+    alpha: f64,
+    n_positive: usize,
+}
+
+impl Stepper for FireStepper {
+    fn propose(&mut self, _positions: &[f64], gradient: &[f64], _max_step: f64) -> Result<Vec<f64>> {
+        let p = &self.params;
+        let force: Vec<_> = gradient.iter().map(|x| -x).collect();
+        if self.velocity.len() != force.len() {
+            self.velocity = vec![0.0; force.len()];
+        } else if dot(&force, &self.velocity) > 0.0 {
+            let vnorm = norm(&self.velocity);
+            let fnorm = norm(&force);
+            if fnorm > 0.0 {
+                let a = self.alpha;
+                self.velocity
+                    .iter_mut()
+                    .zip(&force)
+                    .for_each(|(v, f)| *v = (1.0 - a) * *v + a * f / fnorm * vnorm);
+            }
+            if self.n_positive > p.n_min {
+                self.dt = (self.dt * p.f_inc).min(p.dt_max);
+                self.alpha *= p.f_alpha;
+            }
+            self.n_positive += 1;
+        } else {
+            self.velocity.iter_mut().for_each(|v| *v = 0.0);
+            self.alpha = p.alpha;
+            self.dt *= p.f_dec;
+            self.n_positive = 0;
+        }
+
+        let dt = self.dt;
+        self.velocity.iter_mut().zip(&force).for_each(|(v, f)| *v += dt * f);
+        Ok(self.velocity.iter().map(|v| v * dt).collect())
+    }
+
+    fn accept(&mut self, _energy_change: f64) -> bool {
+        true
+    }
+
+    fn update(&mut self, _s: &[f64], _y: &[f64]) {}
+}
+// f6c13b7a ends here
+
+// [[file:../xtb.note::b73e5c21][b73e5c21]]
+/// Rational function optimization (RFO) in redundant internal coordinates
+/// built from covalent connectivity, with model Hessian guess and BFGS
+/// updates, similar to the ANCopt optimizer in xtb. This is usually much
+/// more efficient than Cartesian optimizers for flexible molecules.
+///
+/// Atoms frozen in [`ConstrainedPotential`](crate::constraints::ConstrainedPotential)
+/// are kept out of the internal coordinate steps.
+#[derive(Clone, Debug)]
+pub struct InternalOpt {
+    driver: OptDriver,
+    atom_types: Vec<i32>,
+    trust_radius: f64,
+    max_trust_radius: f64,
+}
+
+impl_opt_driver_settings!(InternalOpt);
+
+impl InternalOpt {
+    /// Create optimizer for molecule with `atom_types` in atomic numbers.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        for &z in atom_types {
+            ensure!(crate::elements::covalent_radius(z).is_some(), "unsupported element: {z}");
+        }
+        let opt = Self {
+            driver: OptDriver::default(),
+            atom_types: atom_types.to_vec(),
+            trust_radius: 0.3,
+            max_trust_radius: 1.0,
+        };
+        Ok(opt)
+    }
+
+    /// Set initial trust radius of steps in internal coordinates.
+    pub fn trust_radius(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid trust radius: {}", x);
+        self.trust_radius = x;
+        self
+    }
+
+    /// Set maximum trust radius of steps in internal coordinates.
+    pub fn max_trust_radius(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid trust radius: {}", x);
+        self.max_trust_radius = x;
+        self
+    }
+
+    pub(crate) fn stepper(&self) -> InternalStepper {
+        InternalStepper {
+            atom_types: self.atom_types.clone(),
+            frozen: vec![],
+            internals: None,
+            hessian: DMatrix::zeros(0, 0),
+            trust: self.trust_radius,
+            max_trust: self.max_trust_radius,
+            x: vec![],
+            g: vec![],
+            gq: DVector::zeros(0),
+            predicted: 0.0,
+            step_norm: 0.0,
+        }
+    }
+}
+
+pub(crate) struct InternalStepper {
+    atom_types: Vec<i32>,
+    frozen: Vec<usize>,
+    internals: Option<RedundantInternals>,
+    // Hessian in internal coordinates
+    hessian: DMatrix<f64>,
+    trust: f64,
+    max_trust: f64,
+    // positions, gradient and unprojected internal gradient in last proposal
+    x: Vec<f64>,
+    g: Vec<f64>,
+    gq: DVector<f64>,
+    // predicted energy change and length of last step
+    predicted: f64,
+    step_norm: f64,
+}
+
+impl InternalStepper {
+    fn rebuild(&mut self, positions: &[f64]) -> Result<()> {
+        let mut internals = RedundantInternals::new(&self.atom_types, positions)?;
+        internals.freeze_atoms(&self.frozen);
+        self.hessian = DMatrix::from_diagonal(&DVector::from_vec(internals.model_hessian(positions)));
+        self.internals = Some(internals);
+        Ok(())
+    }
+
+    // Angles becoming linear make the internal coordinates ill-defined
+    fn needs_rebuild(&self, positions: &[f64]) -> bool {
+        self.internals.as_ref().map_or(true, |internals| {
+            internals.coords().iter().any(|q| {
+                matches!(q, crate::internal::InternalCoordinate::Angle(..))
+                    && q.value(positions).to_degrees() > crate::internal::LINEAR_ANGLE
+            })
+        })
+    }
+}
+
+impl Stepper for InternalStepper {
+    fn init(&mut self, positions: &[f64], frozen: &[usize]) -> Result<()> {
+        ensure!(
+            positions.len() == 3 * self.atom_types.len(),
+            "positions do not match {} atoms",
+            self.atom_types.len()
+        );
+        let natoms = self.atom_types.len();
+        for &i in frozen {
+            ensure!(i < natoms, "invalid frozen atom {i} for {natoms} atoms");
+        }
+        self.frozen = frozen.to_vec();
+        self.rebuild(positions)
+    }
+
+    fn propose(&mut self, positions: &[f64], gradient: &[f64], _max_step: f64) -> Result<Vec<f64>> {
+        if self.needs_rebuild(positions) {
+            self.rebuild(positions)?;
+        }
+        let internals = self.internals.as_ref().unwrap();
+        let gq = internals.internal_gradient(positions, gradient);
+        let m = gq.len();
+
+        // project Hessian and gradient onto the non-redundant space
+        let b = internals.wilson_b(positions);
+        let g = &b * b.transpose();
+        let p = &g * crate::internal::pseudo_inverse(g.clone(), 1e-6);
+        let q = DMatrix::identity(m, m) - &p;
+        let h = &p * &self.hessian * &p + q * 1000.0;
+        // keep the raw internal gradient for the BFGS update
+        self.gq = gq.clone();
+        let gq = &p * gq;
+
+        // rational function step from the lowest eigenvector of augmented Hessian
+        let mut aug = DMatrix::zeros(m + 1, m + 1);
+        aug.slice_mut((0, 0), (m, m)).copy_from(&h);
+        aug.slice_mut((0, m), (m, 1)).copy_from(&gq);
+        aug.slice_mut((m, 0), (1, m)).copy_from(&gq.transpose());
+        let eigen = aug.symmetric_eigen();
+        let imin = eigen.eigenvalues.imin();
+        let v = eigen.eigenvectors.column(imin);
+        let mut dq = if v[m].abs() > 1e-8 {
+            v.rows(0, m) / v[m]
+        } else {
+            -gq.component_div(&h.diagonal())
+        };
+        dq = &p * dq;
+        let dq_norm = dq.norm();
+        if dq_norm > self.trust {
+            dq *= self.trust / dq_norm;
+        }
+        self.predicted = gq.dot(&dq) + 0.5 * dq.dot(&(&h * &dq));
+        self.step_norm = dq.norm();
+        self.x = positions.to_vec();
+        self.g = gradient.to_vec();
+
+        let d = match internals.cartesian_step(positions, dq.as_slice()) {
+            Some(d) => d,
+            None => {
+                // restart with steepest descent if back-transformation failed
+                self.internals = None;
+                gradient.iter().map(|x| -x).collect()
+            }
+        };
+        Ok(d)
+    }
+
+    fn accept(&mut self, energy_change: f64) -> bool {
+        if energy_change > 1e-6 && self.trust > 1e-3 {
+            self.trust = (0.5 * self.trust.min(self.step_norm)).max(1e-3);
+            return false;
+        }
+        let ratio = energy_change / self.predicted;
+        if ratio > 0.75 && self.step_norm > 0.8 * self.trust {
+            self.trust = (2.0 * self.trust).min(self.max_trust);
+        } else if ratio < 0.25 {
+            self.trust = (0.5 * self.trust).max(1e-3);
+        }
+        true
+    }
+
+    fn update(&mut self, s: &[f64], y: &[f64]) {
+        let internals = match self.internals.as_ref() {
+            Some(internals) if !self.x.is_empty() => internals,
+            _ => return,
+        };
+        let x_new: Vec<_> = self.x.iter().zip(s).map(|(a, b)| a + b).collect();
+        let g_new: Vec<_> = self.g.iter().zip(y).map(|(a, b)| a + b).collect();
+        let g_new = internals.internal_gradient(&x_new, &g_new);
+        let sq = DVector::from_vec(internals.difference(&internals.values(&x_new), &internals.values(&self.x)));
+        let yq = g_new - &self.gq;
+        // BFGS update, skipped if the curvature condition fails
+        let sy = sq.dot(&yq);
+        let hs = &self.hessian * &sq;
+        let shs = sq.dot(&hs);
+        if sy > 1e-8 && shs > 1e-8 {
+            self.hessian += &yq * yq.transpose() / sy - &hs * hs.transpose() / shs;
+        }
+    }
+}
+// b73e5c21 ends here
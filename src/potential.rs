@@ -0,0 +1,50 @@
+// [[file:../xtb.note::6c0e2b84][6c0e2b84]]
+//! Common interface for evaluation of energy and gradient
+// 6c0e2b84 ends here
+
+// [[file:../xtb.note::a1f5d37e][a1f5d37e]]
+use super::*;
+// a1f5d37e ends here
+
+// [[file:../xtb.note::90b4e6c2][90b4e6c2]]
+/// Potential energy surface to be explored by optimizers or dynamics
+/// drivers. All quantities are in atomic units.
+pub trait Potential {
+    /// Evaluate energy in Hartree and gradient in Hartree / Bohr at
+    /// `positions` in Bohr.
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64>;
+
+    /// Return atoms (zero-based) held fixed by the potential, which
+    /// optimizers stepping in internal coordinates have to respect.
+    fn frozen_atoms(&self) -> &[usize] {
+        &[]
+    }
+}
+
+impl Potential for XtbModel {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        self.update_structure(positions, None)?;
+        self.calculate_energy_and_gradient(gradient)
+    }
+}
+
+impl<P: Potential + ?Sized> Potential for &mut P {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        (**self).evaluate(positions, gradient)
+    }
+
+    fn frozen_atoms(&self) -> &[usize] {
+        (**self).frozen_atoms()
+    }
+}
+
+impl<P: Potential + ?Sized> Potential for Box<P> {
+    fn evaluate(&mut self, positions: &[f64], gradient: &mut [f64]) -> Result<f64> {
+        (**self).evaluate(positions, gradient)
+    }
+
+    fn frozen_atoms(&self) -> &[usize] {
+        (**self).frozen_atoms()
+    }
+}
+// 90b4e6c2 ends here
@@ -1,6 +1,9 @@
 // [[file:../xtb.note::fb6f72a1][fb6f72a1]]
 use super::*;
 
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
 // fb6f72a1 ends here
 
@@ -21,23 +24,44 @@ impl XtbEnvironment {
         }
     }
 
-    /// Check current status of calculation environment.
+    /// Check current status of calculation environment. The error stack will
+    /// be emptied and returned as `XtbError`.
     pub fn check_error(&self) -> Result<()> {
+        self.check_status(None)
+    }
+
+    /// Check current status of calculation environment after calling the
+    /// API function `call`.
+    fn check_call(&self, call: &str) -> Result<()> {
+        self.check_status(call.into())
+    }
+
+    fn check_status(&self, call: Option<&str>) -> Result<()> {
         let ret = unsafe { xtb_checkEnvironment(self.env) };
         if ret != 0 {
-            // Show and empty error stack
-            unsafe { xtb_showEnvironment(self.env, null()) };
-            bail!("Error occured in the API with return code {}!", ret);
+            let log = self.get_error();
+            return Err(XtbError::new(ret, call, &log).into());
         }
         Ok(())
     }
 
+    /// Return and empty error stack.
+    fn get_error(&self) -> String {
+        let mut buffer = vec![0u8; 4096];
+        let size = buffer.len() as i32;
+        unsafe {
+            xtb_getError(self.env, buffer.as_mut_ptr() as *mut c_char, &size);
+        }
+        let n = buffer.iter().position(|&x| x == 0).unwrap_or(buffer.len());
+        String::from_utf8_lossy(&buffer[..n]).into_owned()
+    }
+
     /// Set verbosity of calculation output.
     fn set_verbosity(&self, verbosity: u32) -> Result<()> {
         unsafe {
             xtb_setVerbosity(self.env, verbosity as i32);
         }
-        self.check_error()?;
+        self.check_call("xtb_setVerbosity")?;
         Ok(())
     }
 
@@ -55,13 +79,150 @@ impl XtbEnvironment {
     pub fn set_output_muted(&self) -> Result<()> {
         self.set_verbosity(XTB_VERBOSITY_MUTED)
     }
+
+    /// Bind calculation output from this environment to file `path`. The
+    /// file will be overwritten.
+    pub fn set_output_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let filename = path
+            .to_str()
+            .ok_or_else(|| format_err!("invalid output file path: {:?}", path))?;
+        let filename = CString::new(filename)?;
+        unsafe {
+            xtb_setOutput(self.env, filename.as_ptr());
+        }
+        self.check_call("xtb_setOutput")?;
+        Ok(())
+    }
+
+    /// Release output unit from this environment. The calculation output will
+    /// be written to stdout again.
+    pub fn release_output(&self) -> Result<()> {
+        unsafe {
+            xtb_releaseOutput(self.env);
+        }
+        self.check_call("xtb_releaseOutput")?;
+        Ok(())
+    }
 }
 // 8cd490ab ends here
 
+// [[file:../xtb.note::6b0f2d9c][6b0f2d9c]]
+/// Category of failures reported by xtb API.
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum XtbErrorKind {
+    /// Self-consistent charge iterations did not converge
+    SccNotConverged,
+    /// Feature not available with periodic boundary conditions
+    UnsupportedPeriodic,
+    /// Parameter file could not be found or read
+    MissingParameterFile,
+    /// Solvation model not available for current setup
+    UnsupportedSolvation,
+    /// Any other failure
+    Other,
+}
+
+impl XtbErrorKind {
+    /// Guess error category from error message
+    fn from_message(message: &str) -> Self {
+        let msg = message.to_lowercase();
+        if msg.contains("converge") {
+            XtbErrorKind::SccNotConverged
+        } else if msg.contains("pbc") || msg.contains("periodic") {
+            XtbErrorKind::UnsupportedPeriodic
+        } else if msg.contains("param") && (msg.contains("file") || msg.contains("found")) {
+            XtbErrorKind::MissingParameterFile
+        } else if msg.contains("solva") || msg.contains("solvent") {
+            XtbErrorKind::UnsupportedSolvation
+        } else {
+            XtbErrorKind::Other
+        }
+    }
+}
+
+/// Error drained from the error stack of xtb calculation environment.
+#[derive(Clone, Debug)]
+pub struct XtbError {
+    code: i32,
+    call: Option<String>,
+    kind: XtbErrorKind,
+    messages: Vec<String>,
+}
+
+impl XtbError {
+    fn new(code: i32, call: Option<&str>, log: &str) -> Self {
+        // error log lines could be formatted as "-1- source: message"
+        let messages: Vec<_> = log
+            .lines()
+            .map(|line| {
+                let line = line.trim();
+                let line = line.strip_prefix("[ERROR]").unwrap_or(line).trim_start();
+                match line.split_once(' ') {
+                    Some((n, rest)) if n.starts_with('-') && n.ends_with('-') => rest.trim_start(),
+                    _ => line,
+                }
+            })
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_owned())
+            .collect();
+        let kind = messages
+            .iter()
+            .map(|m| XtbErrorKind::from_message(m))
+            .find(|k| *k != XtbErrorKind::Other)
+            .unwrap_or(XtbErrorKind::Other);
+
+        Self {
+            code,
+            call: call.map(|x| x.to_owned()),
+            kind,
+            messages,
+        }
+    }
+
+    /// Return error category.
+    pub fn kind(&self) -> XtbErrorKind {
+        self.kind
+    }
+
+    /// Return the name of API function which failed, if known.
+    pub fn call(&self) -> Option<&str> {
+        self.call.as_deref()
+    }
+
+    /// Return the status code of calculation environment.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Return error messages in the error stack.
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Return error messages joined in a single line.
+    pub fn message(&self) -> String {
+        self.messages.join("; ")
+    }
+}
+
+impl std::fmt::Display for XtbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.call {
+            Some(call) => write!(f, "{} failed ({:?}): {}", call, self.kind, self.message()),
+            None => write!(f, "xtb API error ({:?}): {}", self.kind, self.message()),
+        }
+    }
+}
+
+impl std::error::Error for XtbError {}
+// 6b0f2d9c ends here
+
 // [[file:../xtb.note::3bbaae4e][3bbaae4e]]
 /// Molecular structure data
 pub struct XtbMolecule {
     mol: xtb_TMolecule,
+    natoms: usize,
 }
 
 impl XtbMolecule {
@@ -77,8 +238,10 @@ impl XtbMolecule {
         lattice: impl Into<Option<&'a [f64; 9]>>,
         periodic: impl Into<Option<&'a [bool; 3]>>,
     ) -> Result<Self> {
+        let natoms = attyp.len();
+        ensure!(coord.len() == natoms * 3, "Dimension missmatch between numbers and positions");
         let mol = unsafe {
-            let natoms = attyp.len() as i32;
+            let natoms = natoms as i32;
             let env = env.env;
             let attyp = attyp.as_ptr();
             let coord = coord.as_ptr();
@@ -86,14 +249,15 @@ impl XtbMolecule {
             let periodic = periodic.into().map_or(null(), |x| x.as_ptr());
             xtb_newMolecule(env, &natoms, attyp, coord, &charge, &uhf, lattice, periodic)
         };
-        env.check_error()?;
-        let mol = Self { mol };
+        env.check_call("xtb_newMolecule")?;
+        let mol = Self { mol, natoms };
 
         Ok(mol)
     }
 
     /// Update coordinates and lattice parameters (quantities in Bohr)
     pub fn update(&self, env: &XtbEnvironment, coord: &[f64], lattice: Option<&[f64; 9]>) -> Result<()> {
+        ensure!(coord.len() == self.natoms * 3, "invalid size of positions: {}", coord.len());
         unsafe {
             let env = env.env;
             let mol = self.mol;
@@ -101,10 +265,15 @@ impl XtbMolecule {
             let lattice = lattice.map_or(null(), |x| x.as_ptr());
             xtb_updateMolecule(env, mol, coord, lattice);
         }
-        env.check_error()?;
+        env.check_call("xtb_updateMolecule")?;
 
         Ok(())
     }
+
+    /// Return the number of atoms.
+    pub fn natoms(&self) -> usize {
+        self.natoms
+    }
 }
 // 3bbaae4e ends here
 
@@ -137,19 +306,56 @@ impl XtbCalculator {
 
     /// Load parametrization of GFN-xTB method `method`.
     pub fn load_parametrization(&self, mol: &XtbMolecule, env: &XtbEnvironment, method: XtbMethod) -> Result<()> {
-        unsafe {
+        self.load_parametrization_from(mol, env, method, None)
+    }
+
+    /// Load parametrization of GFN-xTB method `method` from parameter file
+    /// `param_file`. If `param_file` is None, the default parameter file will
+    /// be searched in directories set by `XTBPATH` env var when required.
+    pub fn load_parametrization_from<'a>(
+        &self,
+        mol: &XtbMolecule,
+        env: &XtbEnvironment,
+        method: XtbMethod,
+        param_file: impl Into<Option<&'a Path>>,
+    ) -> Result<()> {
+        let param_file = check_parameter_file(method, param_file.into())?;
+        let filename = match param_file {
+            Some(path) => {
+                let path = path
+                    .to_str()
+                    .ok_or_else(|| format_err!("invalid parameter file path: {:?}", path))?;
+                Some(CString::new(path)?)
+            }
+            None => None,
+        };
+        let call = unsafe {
             let calc = self.calc;
             let mol = mol.mol;
             let env = env.env;
+            let filename = filename
+                .as_ref()
+                .map_or(std::ptr::null_mut(), |x| x.as_ptr() as *mut c_char);
             match method {
-                XtbMethod::GFNFF => xtb_loadGFNFF(env, mol, calc, std::ptr::null_mut()),
-                XtbMethod::GFN0xTB => xtb_loadGFN0xTB(env, mol, calc, std::ptr::null_mut()),
-                XtbMethod::GFN1xTB => xtb_loadGFN1xTB(env, mol, calc, std::ptr::null_mut()),
-                XtbMethod::GFN2xTB => xtb_loadGFN2xTB(env, mol, calc, std::ptr::null_mut()),
-                _ => unimplemented!(),
+                XtbMethod::GFNFF => {
+                    xtb_loadGFNFF(env, mol, calc, filename);
+                    "xtb_loadGFNFF"
+                }
+                XtbMethod::GFN0xTB => {
+                    xtb_loadGFN0xTB(env, mol, calc, filename);
+                    "xtb_loadGFN0xTB"
+                }
+                XtbMethod::GFN1xTB => {
+                    xtb_loadGFN1xTB(env, mol, calc, filename);
+                    "xtb_loadGFN1xTB"
+                }
+                XtbMethod::GFN2xTB => {
+                    xtb_loadGFN2xTB(env, mol, calc, filename);
+                    "xtb_loadGFN2xTB"
+                }
             }
-        }
-        env.check_error()?;
+        };
+        env.check_call(call)?;
         Ok(())
     }
 
@@ -182,6 +388,15 @@ impl XtbCalculator {
     /// overwritten by default.
     pub fn single_point(&self, mol: &XtbMolecule, env: &XtbEnvironment) -> Result<XtbResults> {
         let mut res = XtbResults::new();
+        self.single_point_into(mol, env, &mut res)?;
+        Ok(res)
+    }
+
+    /// Perform singlepoint calculation, storing results into `res`. The
+    /// wavefunction kept in `res` from a previous calculation will be used
+    /// as initial guess, which could speed up SCC convergence for similar
+    /// geometries.
+    pub fn single_point_into(&self, mol: &XtbMolecule, env: &XtbEnvironment, res: &mut XtbResults) -> Result<()> {
         unsafe {
             let calc = self.calc;
             let mol = mol.mol;
@@ -189,117 +404,391 @@ impl XtbCalculator {
             let env = env.env;
             xtb_singlepoint(env, mol, calc, res);
         }
-        env.check_error()?;
-        Ok(res)
+        env.check_call("xtb_singlepoint")?;
+        res.natoms = mol.natoms;
+        Ok(())
     }
 }
 // e737b33d ends here
 
+// [[file:../xtb.note::4e7b0a19][4e7b0a19]]
+impl XtbMethod {
+    /// Return the name of default parameter file for this method.
+    pub fn parameter_file_name(&self) -> &'static str {
+        match self {
+            XtbMethod::GFN2xTB => "param_gfn2-xtb.txt",
+            XtbMethod::GFN1xTB => "param_gfn1-xtb.txt",
+            XtbMethod::GFN0xTB => "param_gfn0-xtb.txt",
+            XtbMethod::GFNFF => ".param_gfnff.xtb",
+        }
+    }
+
+    /// Return true if the parameters are not built in libxtb, and have to be
+    /// read from external parameter file.
+    fn requires_parameter_file(&self) -> bool {
+        matches!(self, XtbMethod::GFN0xTB)
+    }
+}
+
+/// Directories to be searched for default parameter files, following the
+/// convention of xtb program: `XTBPATH`, `XTBHOME` and current directory.
+fn parameter_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Some(xtbpath) = std::env::var_os("XTBPATH") {
+        paths.extend(std::env::split_paths(&xtbpath));
+    }
+    if let Some(xtbhome) = std::env::var_os("XTBHOME") {
+        let xtbhome = PathBuf::from(xtbhome);
+        paths.push(xtbhome.join("share").join("xtb"));
+        paths.push(xtbhome);
+    }
+    paths.push(PathBuf::from("."));
+    paths
+}
+
+/// Make sure the parameter file of `method` is available before loading
+/// parametrization. Return the resolved path of parameter file if required.
+fn check_parameter_file(method: XtbMethod, param_file: Option<&Path>) -> Result<Option<PathBuf>> {
+    if let Some(path) = param_file {
+        ensure!(path.is_file(), "parameter file for {:?} not found: {:?}", method, path);
+        return Ok(Some(path.to_owned()));
+    }
+
+    if method.requires_parameter_file() {
+        let name = method.parameter_file_name();
+        let paths = parameter_search_paths();
+        let path = paths.iter().map(|d| d.join(name)).find(|p| p.is_file());
+        ensure!(
+            path.is_some(),
+            "parameter file {:?} for {:?} not found in {:?}; please set XTBPATH env var or provide the file explicitly",
+            name,
+            method,
+            paths
+        );
+        return Ok(path);
+    }
+
+    Ok(None)
+}
+// 4e7b0a19 ends here
+
+// [[file:../xtb.note::5c1e7f3a][5c1e7f3a]]
+/// Reference state of the solution for implicit solvation.
+#[derive(Clone, Debug, Copy, PartialEq, Default)]
+pub enum XtbSolutionState {
+    /// 1 L of ideal gas and 1 L of ideal solution
+    #[default]
+    Gsolv,
+    /// 1 bar of ideal gas and 1 mol/L of liquid solution at infinite dilution
+    Reference,
+    /// 1 bar of ideal gas and 1 mol/L of ideal solution
+    Mol1Bar,
+}
+
+impl XtbCalculator {
+    /// Add a GBSA implicit solvation model for `solvent` to calculator. The
+    /// parametrization must be loaded before calling this method, and it has
+    /// to be applied again whenever the parametrization is reloaded. The
+    /// solvent name is checked by xtb against its parameter files. ALPB is
+    /// not available from the C API of xtb.
+    ///
+    /// # Parameters
+    ///
+    /// * state: reference state of the solution, default to `Gsolv`
+    /// * temperature: temperature of the solution in K, default to 298.15 K
+    /// * grid: number of angular grid points for SASA calculation, default to 230
+    pub fn set_solvent(
+        &self,
+        env: &XtbEnvironment,
+        solvent: &str,
+        state: impl Into<Option<XtbSolutionState>>,
+        temperature: impl Into<Option<f64>>,
+        grid: impl Into<Option<usize>>,
+    ) -> Result<()> {
+        let name = CString::new(solvent.trim())?;
+
+        let mut state = match state.into().unwrap_or_default() {
+            XtbSolutionState::Gsolv => 1,
+            XtbSolutionState::Reference => 2,
+            XtbSolutionState::Mol1Bar => 3,
+        };
+        let mut temperature = temperature.into().unwrap_or(298.15);
+        let mut grid = grid.into().unwrap_or(230) as i32;
+        unsafe {
+            xtb_setSolvent(
+                env.env,
+                self.calc,
+                name.as_ptr() as *mut _,
+                &mut state,
+                &mut temperature,
+                &mut grid,
+            );
+        }
+        env.check_call("xtb_setSolvent")?;
+        Ok(())
+    }
+
+    /// Unset the solvation model.
+    pub fn release_solvent(&self, env: &XtbEnvironment) -> Result<()> {
+        unsafe {
+            xtb_releaseSolvent(env.env, self.calc);
+        }
+        env.check_call("xtb_releaseSolvent")?;
+        Ok(())
+    }
+}
+// 5c1e7f3a ends here
+
+// [[file:../xtb.note::a3d94e07][a3d94e07]]
+impl XtbCalculator {
+    /// Add an external point charge potential to calculator (only supported
+    /// in GFN1-xTB and GFN2-xTB). The parametrization must be loaded before
+    /// calling this method.
+    ///
+    /// # Parameters
+    ///
+    /// * numbers: atomic numbers of point charges, used for chemical hardness
+    /// * charges: point charges in e
+    /// * positions: positions of point charges in Bohr
+    pub fn set_external_charges(
+        &self,
+        env: &XtbEnvironment,
+        numbers: &[i32],
+        charges: &[f64],
+        positions: &[f64],
+    ) -> Result<()> {
+        let n = numbers.len();
+        ensure!(
+            charges.len() == n && positions.len() == n * 3,
+            "Dimension missmatch between numbers, charges and positions of external charges"
+        );
+        let mut n = n as i32;
+        unsafe {
+            xtb_setExternalCharges(
+                env.env,
+                self.calc,
+                &mut n,
+                numbers.as_ptr() as *mut _,
+                charges.as_ptr() as *mut _,
+                positions.as_ptr() as *mut _,
+            );
+        }
+        env.check_call("xtb_setExternalCharges")?;
+        Ok(())
+    }
+
+    /// Unset the external charge potential.
+    pub fn release_external_charges(&self, env: &XtbEnvironment) -> Result<()> {
+        unsafe {
+            xtb_releaseExternalCharges(env.env, self.calc);
+        }
+        env.check_call("xtb_releaseExternalCharges")?;
+        Ok(())
+    }
+}
+// a3d94e07 ends here
+
 // [[file:../xtb.note::1e3dd6ef][1e3dd6ef]]
 /// XTB singlepoint results object
 pub struct XtbResults {
     res: xtb_TResults,
+    // number of atoms of the molecule computed for
+    natoms: usize,
 }
 
 impl XtbResults {
     /// Create new singlepoint results object
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             res: unsafe { xtb_newResults() },
+            natoms: 0,
+        }
+    }
+
+    /// Return the number of atoms of the molecule in calculation. Return
+    /// None if no calculation done yet.
+    pub fn natoms(&self) -> Option<usize> {
+        if self.natoms > 0 {
+            Some(self.natoms)
+        } else {
+            None
         }
     }
 
     /// Get singlepoint energy in Hartree
     pub fn get_energy(&self, env: &XtbEnvironment) -> Result<f64> {
-        let mut energy = std::f64::NAN;
+        let mut energy = f64::NAN;
         unsafe {
             xtb_getEnergy(env.env, self.res, &mut energy);
         }
-        env.check_error()?;
+        env.check_call("xtb_getEnergy")?;
         Ok(energy)
     }
 
     /// Get dipole in e Bohr
     pub fn get_dipole(&self, env: &XtbEnvironment) -> Result<[f64; 3]> {
-        let mut dipole = [std::f64::NAN; 3];
+        let mut dipole = [f64::NAN; 3];
         unsafe {
             xtb_getDipole(env.env, self.res, dipole.as_mut_ptr());
         }
-        env.check_error()?;
+        env.check_call("xtb_getDipole")?;
         Ok(dipole)
     }
 
-    /// Get gradient in Hartree / Bohr
-    pub fn get_gradient(&self, env: &XtbEnvironment, gradient: &mut [f64]) -> Result<()> {
+    /// Query singlepoint results object for the number of basis functions
+    pub fn get_nao(&self, env: &XtbEnvironment) -> Result<usize> {
+        let mut nao = 0;
         unsafe {
-            xtb_getGradient(env.env, self.res, gradient.as_mut_ptr());
+            xtb_getNao(env.env, self.res, &mut nao);
         }
-        env.check_error()?;
-        Ok(())
+        env.check_call("xtb_getNao")?;
+        Ok(nao as usize)
     }
 
-    /// Query singlepoint results object for bond orders
-    pub fn get_bond_orders(&self, env: &XtbEnvironment, bond_orders: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getBondOrders(env.env, self.res, bond_orders.as_mut_ptr());
-        }
-        env.check_error()?;
-        Ok(())
+    fn computed_natoms(&self) -> Result<usize> {
+        self.natoms().ok_or_else(|| format_err!("no singlepoint results available"))
     }
 
-    /// Query singlepoint results object for partial charges in e
-    pub fn get_charges(&self, env: &XtbEnvironment, charges: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getCharges(env.env, self.res, charges.as_mut_ptr());
-        }
-        env.check_error()?;
-        Ok(())
+    fn size_gradient(&self, _env: &XtbEnvironment) -> Result<usize> {
+        Ok(self.computed_natoms()? * 3)
     }
 
-    /// Query singlepoint results object for virial in Hartree
-    pub fn get_virial(&self, env: &XtbEnvironment, virial: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getVirial(env.env, self.res, virial.as_mut_ptr());
-        }
-        env.check_error()?;
-        Ok(())
+    fn size_virial(&self, _env: &XtbEnvironment) -> Result<usize> {
+        Ok(9)
     }
 
-    /// Query singlepoint results object for the number of basis functions
-    pub fn get_nao(&self, env: &XtbEnvironment) -> Result<usize> {
-        let mut nao = 0;
-        unsafe {
-            xtb_getNao(env.env, self.res, &mut nao);
-        }
-        env.check_error()?;
-        Ok(nao as usize)
+    fn size_charges(&self, _env: &XtbEnvironment) -> Result<usize> {
+        self.computed_natoms()
     }
 
-    /// Query singlepoint results object for orbital energies in Hartree [nao]
-    pub fn get_orbital_eigenvalues(&self, env: &XtbEnvironment, emo: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getOrbitalEigenvalues(env.env, self.res, emo.as_mut_ptr());
-        }
-        env.check_error()?;
-        Ok(())
+    fn size_bond_orders(&self, _env: &XtbEnvironment) -> Result<usize> {
+        let n = self.computed_natoms()?;
+        Ok(n * n)
     }
 
-    /// Query singlepoint results object for occupation numbers [nao]
-    pub fn get_orbital_occupations(&self, env: &XtbEnvironment, focc: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getOrbitalOccupations(env.env, self.res, focc.as_mut_ptr());
+    fn size_orbitals(&self, env: &XtbEnvironment) -> Result<usize> {
+        self.computed_natoms()?;
+        self.get_nao(env)
+    }
+
+    fn size_orbital_coefficients(&self, env: &XtbEnvironment) -> Result<usize> {
+        let nao = self.size_orbitals(env)?;
+        Ok(nao * nao)
+    }
+}
+
+/// Implement getters of array quantities in singlepoint results object:
+/// a bounds-checked getter writing into caller's slice, an unchecked
+/// variant, and a getter returning owned `Vec`.
+macro_rules! impl_xtb_results_getter {
+    ($desc:literal, $get:ident, $get_unchecked:ident, $owned:ident, $xtb_get:ident, $size:ident) => {
+        impl XtbResults {
+            #[doc = concat!("Query singlepoint results object for ", $desc, ". Return error if the size of `buffer` mismatches.")]
+            pub fn $get(&self, env: &XtbEnvironment, buffer: &mut [f64]) -> Result<()> {
+                let n = self.$size(env)?;
+                ensure!(
+                    buffer.len() == n,
+                    "invalid buffer size for {}: {}, expected {}",
+                    stringify!($owned),
+                    buffer.len(),
+                    n
+                );
+                unsafe { self.$get_unchecked(env, buffer) }
+            }
+
+            #[doc = concat!("Query singlepoint results object for ", $desc, " without bounds checking.")]
+            ///
+            /// # Safety
+            ///
+            /// `buffer` must be large enough to hold all the data, otherwise
+            /// the memory will be corrupted.
+            pub unsafe fn $get_unchecked(&self, env: &XtbEnvironment, buffer: &mut [f64]) -> Result<()> {
+                $xtb_get(env.env, self.res, buffer.as_mut_ptr());
+                env.check_call(stringify!($xtb_get))?;
+                Ok(())
+            }
+
+            #[doc = concat!("Return ", $desc, " from singlepoint results object.")]
+            pub fn $owned(&self, env: &XtbEnvironment) -> Result<Vec<f64>> {
+                let mut buffer = vec![0.0; self.$size(env)?];
+                unsafe { self.$get_unchecked(env, &mut buffer)? };
+                Ok(buffer)
+            }
         }
-        env.check_error()?;
-        Ok(())
+    };
+}
+
+impl_xtb_results_getter!(
+    "gradient in Hartree / Bohr [natoms][3]",
+    get_gradient,
+    get_gradient_unchecked,
+    gradient,
+    xtb_getGradient,
+    size_gradient
+);
+impl_xtb_results_getter!(
+    "virial in Hartree [3][3]",
+    get_virial,
+    get_virial_unchecked,
+    virial,
+    xtb_getVirial,
+    size_virial
+);
+impl_xtb_results_getter!(
+    "partial charges in e [natoms]",
+    get_charges,
+    get_charges_unchecked,
+    charges,
+    xtb_getCharges,
+    size_charges
+);
+impl_xtb_results_getter!(
+    "Wiberg bond orders [natoms][natoms]",
+    get_bond_orders,
+    get_bond_orders_unchecked,
+    bond_orders,
+    xtb_getBondOrders,
+    size_bond_orders
+);
+impl_xtb_results_getter!(
+    "orbital energies in Hartree [nao]",
+    get_orbital_eigenvalues,
+    get_orbital_eigenvalues_unchecked,
+    orbital_eigenvalues,
+    xtb_getOrbitalEigenvalues,
+    size_orbitals
+);
+impl_xtb_results_getter!(
+    "orbital occupation numbers [nao]",
+    get_orbital_occupations,
+    get_orbital_occupations_unchecked,
+    orbital_occupations,
+    xtb_getOrbitalOccupations,
+    size_orbitals
+);
+impl_xtb_results_getter!(
+    "orbital coefficients [nao][nao]",
+    get_orbital_coefficients,
+    get_orbital_coefficients_unchecked,
+    orbital_coefficients,
+    xtb_getOrbitalCoefficients,
+    size_orbital_coefficients
+);
+
+impl Default for XtbResults {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    /// Query singlepoint results object for orbital coefficients [nao][nao]
-    pub fn get_orbital_coefficients(&self, env: &XtbEnvironment, forb: &mut [f64]) -> Result<()> {
-        unsafe {
-            xtb_getOrbitalCoefficients(env.env, self.res, forb.as_mut_ptr());
+impl Clone for XtbResults {
+    /// Create a copy of singlepoint results object, including the
+    /// wavefunction.
+    fn clone(&self) -> Self {
+        Self {
+            res: unsafe { xtb_copyResults(self.res) },
+            natoms: self.natoms,
         }
-        env.check_error()?;
-        Ok(())
     }
 }
 // 1e3dd6ef ends here
@@ -0,0 +1,71 @@
+// [[file:../xtb.note::5b0e93d7][5b0e93d7]]
+//! Root mean square deviation of structures after optimal superposition
+// 5b0e93d7 ends here
+
+// [[file:../xtb.note::c2e8a614][c2e8a614]]
+use nalgebra::{Matrix3, Vector3};
+// c2e8a614 ends here
+
+// [[file:../xtb.note::7f1d4b09][7f1d4b09]]
+fn centroid(positions: &[f64]) -> Vector3<f64> {
+    let n = (positions.len() / 3) as f64;
+    positions
+        .chunks_exact(3)
+        .fold(Vector3::zeros(), |c, r| c + Vector3::new(r[0], r[1], r[2]) / n)
+}
+
+/// Superimpose `mobile` onto `target` by the Kabsch algorithm without
+/// reflection. Return the translated and rotated positions of `mobile`.
+pub fn superimpose(target: &[f64], mobile: &[f64]) -> Vec<f64> {
+    assert_eq!(target.len(), mobile.len(), "structures have different sizes");
+    assert!(!target.is_empty(), "empty structures");
+    let ct = centroid(target);
+    let cm = centroid(mobile);
+
+    // covariance matrix
+    let mut h = Matrix3::<f64>::zeros();
+    for (p, q) in mobile.chunks_exact(3).zip(target.chunks_exact(3)) {
+        let p = Vector3::new(p[0], p[1], p[2]) - cm;
+        let q = Vector3::new(q[0], q[1], q[2]) - ct;
+        h += p * q.transpose();
+    }
+    let svd = h.svd(true, true);
+    let u = svd.u.expect("svd u");
+    let vt = svd.v_t.expect("svd v_t");
+    let d = (vt.transpose() * u.transpose()).determinant().signum();
+    let rot = vt.transpose() * Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, d)) * u.transpose();
+
+    mobile
+        .chunks_exact(3)
+        .flat_map(|p| {
+            let r = rot * (Vector3::new(p[0], p[1], p[2]) - cm) + ct;
+            [r[0], r[1], r[2]]
+        })
+        .collect()
+}
+
+/// Return RMSD in Bohr between structures `a` and `b` in Bohr after optimal
+/// superposition.
+pub fn rmsd(a: &[f64], b: &[f64]) -> f64 {
+    let b = superimpose(a, b);
+    let n = (a.len() / 3) as f64;
+    let d2: f64 = a.iter().zip(&b).map(|(x, y)| (x - y).powi(2)).sum();
+    (d2 / n).sqrt()
+}
+
+#[test]
+fn test_rmsd() {
+    use approx::assert_relative_eq;
+
+    let a = [0.0, 0.0, 0.0, 1.5, 0.0, 0.0, 0.0, 2.0, 0.0, 0.3, 0.4, 1.1];
+    // rotate by 90 degrees around z and translate
+    let b: Vec<_> = a.chunks(3).flat_map(|r| [-r[1] + 1.0, r[0] - 2.0, r[2] + 0.5]).collect();
+    assert!(rmsd(&a, &b) < 1e-10);
+    let c = superimpose(&a, &b);
+    assert_relative_eq!(c.as_slice(), a.as_slice(), epsilon = 1e-10);
+
+    // mirror image is not superimposable
+    let m: Vec<_> = a.chunks(3).flat_map(|r| [r[0], r[1], -r[2]]).collect();
+    assert!(rmsd(&a, &m) > 0.1);
+}
+// 7f1d4b09 ends here
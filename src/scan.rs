@@ -0,0 +1,302 @@
+// [[file:../xtb.note::4f9a2c61][4f9a2c61]]
+//! Relaxed scan of potential energy surface along one or two internal
+//! coordinates, similar to the `$scan` block of xtb.
+// 4f9a2c61 ends here
+
+// [[file:../xtb.note::b8e1d3a7][b8e1d3a7]]
+use crate::constraints::{ConstrainedPotential, Constraints};
+use crate::elements::ANGSTROM_TO_BOHR;
+use crate::internal::InternalCoordinate;
+use crate::opt::{Lbfgs, OptConvergence};
+use crate::potential::Potential;
+use crate::xyz::write_xyz;
+
+use anyhow::*;
+use std::io::Write;
+use std::path::Path;
+// b8e1d3a7 ends here
+
+// [[file:../xtb.note::39c5f0e2][39c5f0e2]]
+/// Relaxed scan: the scanned coordinates are restrained at each grid point
+/// while all other degrees of freedom are optimized. Each point starts from
+/// the optimized structure of the previous one. Two-dimensional grids are
+/// visited row by row in alternating directions, so that the previous
+/// point is always a neighbour.
+#[derive(Clone, Debug)]
+pub struct Scan {
+    atom_types: Vec<i32>,
+    coords: Vec<(InternalCoordinate, Vec<f64>)>,
+    constraints: Constraints,
+    force_constant: f64,
+    optimizer: Lbfgs,
+}
+
+/// An optimized point of the scan.
+#[derive(Clone, Debug)]
+pub struct ScanPoint {
+    /// Grid indices of this point for each scanned coordinate
+    pub indices: Vec<usize>,
+    /// Target values of scanned coordinates in Bohr or radians
+    pub values: Vec<f64>,
+    /// Energy in Hartree, without restraint energy
+    pub energy: f64,
+    /// Restraint energy in Hartree
+    pub restraint_energy: f64,
+    /// Optimized positions in Bohr
+    pub positions: Vec<f64>,
+    /// Whether the constrained optimization converged
+    pub converged: bool,
+}
+
+/// Final report of a relaxed scan.
+#[derive(Clone, Debug)]
+pub struct ScanReport {
+    /// Atomic numbers of atoms
+    pub atom_types: Vec<i32>,
+    /// Scanned coordinates
+    pub coords: Vec<InternalCoordinate>,
+    /// Optimized points in the order of visit
+    pub points: Vec<ScanPoint>,
+}
+
+impl ScanReport {
+    /// Return true if optimizations at all points converged.
+    pub fn converged(&self) -> bool {
+        self.points.iter().all(|p| p.converged)
+    }
+
+    /// Return the point with the lowest energy.
+    pub fn lowest(&self) -> &ScanPoint {
+        self.points
+            .iter()
+            .min_by(|a, b| a.energy.total_cmp(&b.energy))
+            .expect("empty scan")
+    }
+
+    /// Return points sorted by grid indices.
+    pub fn grid(&self) -> Vec<&ScanPoint> {
+        let mut points: Vec<_> = self.points.iter().collect();
+        points.sort_by(|a, b| a.indices.cmp(&b.indices));
+        points
+    }
+
+    /// Write energy table into `path` in grid order, as columns of scanned
+    /// values in Angstrom or degrees, energy in Hartree, and energy relative
+    /// to the lowest point in kcal/mol. Rows of a 2D scan are separated by
+    /// blank lines as expected by gnuplot.
+    pub fn write_table(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let f = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut f = std::io::BufWriter::new(f);
+        let labels: Vec<_> = self.coords.iter().map(label).collect();
+        writeln!(f, "# {}  E/Eh  dE/(kcal/mol)", labels.join("  "))?;
+        let e0 = self.lowest().energy;
+        let mut row = None;
+        for p in self.grid() {
+            if row.is_some_and(|i| i != p.indices[0]) && p.indices.len() > 1 {
+                writeln!(f)?;
+            }
+            row = Some(p.indices[0]);
+            for (c, &v) in self.coords.iter().zip(&p.values) {
+                write!(f, "{:12.4} ", display_value(c, v))?;
+            }
+            writeln!(f, "{:16.10} {:12.4}", p.energy, (p.energy - e0) * 627.5095)?;
+        }
+        f.flush()?;
+        Ok(())
+    }
+
+    /// Write optimized structures into `path` in xyz format in grid order.
+    /// The comment line of each frame has scanned values and energy.
+    pub fn write_xyz(&self, path: impl AsRef<Path>) -> Result<()> {
+        let frames = self.grid().into_iter().map(|p| {
+            let values: Vec<_> = self
+                .coords
+                .iter()
+                .zip(&p.values)
+                .map(|(c, &v)| format!("{}= {:.4}", label(c), display_value(c, v)))
+                .collect();
+            (format!("{} energy= {:.10}", values.join(" "), p.energy), p.positions.as_slice())
+        });
+        write_xyz(path, &self.atom_types, frames)
+    }
+}
+
+/// Label of internal coordinate with one-based atom indices and unit.
+fn label(coord: &InternalCoordinate) -> String {
+    let atoms: Vec<_> = coord.atoms().iter().map(|i| (i + 1).to_string()).collect();
+    let atoms = atoms.join("-");
+    match coord {
+        InternalCoordinate::Bond(..) => format!("r({atoms})/Angstrom"),
+        InternalCoordinate::Angle(..) => format!("angle({atoms})/degree"),
+        InternalCoordinate::Dihedral(..) => format!("dihedral({atoms})/degree"),
+        InternalCoordinate::LinearBend(..) => format!("linear_bend({atoms})"),
+    }
+}
+
+/// Value of internal coordinate in Angstrom or degrees.
+fn display_value(coord: &InternalCoordinate, value: f64) -> f64 {
+    match coord {
+        InternalCoordinate::Bond(..) => value / ANGSTROM_TO_BOHR,
+        InternalCoordinate::Angle(..) | InternalCoordinate::Dihedral(..) => value.to_degrees(),
+        InternalCoordinate::LinearBend(..) => value,
+    }
+}
+
+/// `n` evenly spaced values from `start` to `end`.
+fn linspace(start: f64, end: f64, n: usize) -> Vec<f64> {
+    assert!(n > 0, "invalid number of points: {}", n);
+    if n == 1 {
+        return vec![start];
+    }
+    let d = (end - start) / (n - 1) as f64;
+    (0..n).map(|i| start + d * i as f64).collect()
+}
+
+impl Scan {
+    /// Create scan for molecule with `atom_types` in atomic numbers.
+    pub fn new(atom_types: &[i32]) -> Self {
+        Self {
+            atom_types: atom_types.to_vec(),
+            coords: vec![],
+            constraints: Constraints::default(),
+            force_constant: 1.0,
+            optimizer: Lbfgs::default(),
+        }
+    }
+
+    /// Scan distance between atom `i` and `j` from `start` to `end` in Bohr
+    /// with `n` points.
+    pub fn distance(&mut self, i: usize, j: usize, start: f64, end: f64, n: usize) -> &mut Self {
+        self.coordinate(InternalCoordinate::Bond(i, j), &linspace(start, end, n))
+    }
+
+    /// Scan angle i-j-k from `start` to `end` in degrees with `n` points.
+    pub fn angle(&mut self, i: usize, j: usize, k: usize, start: f64, end: f64, n: usize) -> &mut Self {
+        let values = linspace(start.to_radians(), end.to_radians(), n);
+        self.coordinate(InternalCoordinate::Angle(i, j, k), &values)
+    }
+
+    /// Scan dihedral angle i-j-k-l from `start` to `end` in degrees with `n`
+    /// points. The range may exceed 180 degrees, for example from 0 to 360.
+    #[allow(clippy::too_many_arguments)]
+    pub fn dihedral(&mut self, i: usize, j: usize, k: usize, l: usize, start: f64, end: f64, n: usize) -> &mut Self {
+        let values = linspace(start.to_radians(), end.to_radians(), n);
+        self.coordinate(InternalCoordinate::Dihedral(i, j, k, l), &values)
+    }
+
+    /// Scan general internal `coord` through `values` in Bohr or radians. At
+    /// most two coordinates could be scanned together.
+    pub fn coordinate(&mut self, coord: InternalCoordinate, values: &[f64]) -> &mut Self {
+        assert!(!values.is_empty(), "no values to scan");
+        self.coords.push((coord, values.to_vec()));
+        self
+    }
+
+    /// Set additional `constraints` kept during the scan. Restraints
+    /// without target keep their values in the starting structure.
+    pub fn constraints(&mut self, constraints: Constraints) -> &mut Self {
+        self.constraints = constraints;
+        self
+    }
+
+    /// Set force constant for restraining scanned coordinates, in the same
+    /// units as [`Constraints::force_constant`]. The default is 1.0, stiff
+    /// enough to hold bonds close to their targets.
+    pub fn force_constant(&mut self, k: f64) -> &mut Self {
+        assert!(k > 0.0, "invalid force constant: {}", k);
+        self.force_constant = k;
+        self
+    }
+
+    /// Set convergence criteria of the optimization at each point.
+    pub fn convergence(&mut self, convergence: OptConvergence) -> &mut Self {
+        self.optimizer.convergence(convergence);
+        self
+    }
+
+    /// Set maximum number of optimization steps at each point.
+    pub fn max_steps(&mut self, n: usize) -> &mut Self {
+        self.optimizer.max_steps(n);
+        self
+    }
+
+    /// Run scan on `pot` starting from `positions` in Bohr.
+    pub fn run<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<ScanReport> {
+        self.run_with(pot, positions, |_| true)
+    }
+
+    /// Run scan on `pot` starting from `positions` in Bohr, and call
+    /// `callback` after each point. The scan stops early if `callback`
+    /// returns false.
+    pub fn run_with<P, F>(&self, pot: &mut P, positions: &[f64], mut callback: F) -> Result<ScanReport>
+    where
+        P: Potential + ?Sized,
+        F: FnMut(&ScanPoint) -> bool,
+    {
+        ensure!(!self.coords.is_empty(), "no coordinate to scan");
+        ensure!(self.coords.len() <= 2, "cannot scan more than two coordinates");
+        ensure!(positions.len() == 3 * self.atom_types.len(), "positions do not match atom types");
+
+        // restraints without target are kept at the starting structure
+        let mut base = self.constraints.clone();
+        base.check(self.atom_types.len())?;
+        base.set_targets_from(positions);
+
+        let mut points = vec![];
+        let mut x = positions.to_vec();
+        for indices in self.grid_order() {
+            let values: Vec<_> = indices.iter().zip(&self.coords).map(|(&i, (_, v))| v[i]).collect();
+            let mut constraints = base.clone();
+            for ((coord, _), &v) in self.coords.iter().zip(&values) {
+                constraints.restrain(*coord, v, self.force_constant);
+            }
+            let mut constrained = ConstrainedPotential::new(&mut *pot, constraints.clone());
+            let report = self.optimizer.minimize(&mut constrained, &x)?;
+            let mut g = vec![0.0; x.len()];
+            let restraint_energy = constraints.apply(&report.positions, &mut g);
+            x.clone_from(&report.positions);
+            let point = ScanPoint {
+                indices,
+                values,
+                energy: report.energy - restraint_energy,
+                restraint_energy,
+                converged: report.converged(),
+                positions: report.positions,
+            };
+            let proceed = callback(&point);
+            points.push(point);
+            if !proceed {
+                break;
+            }
+        }
+
+        let report = ScanReport {
+            atom_types: self.atom_types.clone(),
+            coords: self.coords.iter().map(|(c, _)| *c).collect(),
+            points,
+        };
+        Ok(report)
+    }
+
+    /// Grid indices in the order of visit.
+    fn grid_order(&self) -> Vec<Vec<usize>> {
+        let n0 = self.coords[0].1.len();
+        match self.coords.get(1) {
+            None => (0..n0).map(|i| vec![i]).collect(),
+            Some((_, v)) => {
+                let n1 = v.len();
+                let mut order = vec![];
+                for i in 0..n0 {
+                    if i % 2 == 0 {
+                        order.extend((0..n1).map(|j| vec![i, j]));
+                    } else {
+                        order.extend((0..n1).rev().map(|j| vec![i, j]));
+                    }
+                }
+                order
+            }
+        }
+    }
+}
+// 39c5f0e2 ends here
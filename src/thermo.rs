@@ -0,0 +1,297 @@
+// [[file:../xtb.note::1d9e6b42][1d9e6b42]]
+//! Thermochemistry in rigid rotor harmonic oscillator (RRHO) approximation
+// 1d9e6b42 ends here
+
+// [[file:../xtb.note::7c2b0f95][7c2b0f95]]
+use crate::hessian::NormalModes;
+
+use anyhow::*;
+use nalgebra::Matrix3;
+use std::f64::consts::PI;
+// 7c2b0f95 ends here
+
+// [[file:../xtb.note::e5a34d18][e5a34d18]]
+// physical constants in SI units
+const PLANCK: f64 = 6.62607015e-34;
+const BOLTZMANN: f64 = 1.380649e-23;
+const SPEED_OF_LIGHT: f64 = 2.99792458e10; // in cm/s
+const AMU: f64 = 1.66053906660e-27;
+const HARTREE: f64 = 4.3597447222071e-18;
+const BOHR: f64 = 0.529177210903e-10;
+const AVOGADRO: f64 = 6.02214076e23;
+const CALORIE: f64 = 4.184;
+
+/// Return principal moments of inertia in amu Bohr^2 in ascending order for
+/// atoms with `masses` in amu at `positions` in Bohr.
+pub fn moments_of_inertia(masses: &[f64], positions: &[f64]) -> [f64; 3] {
+    principal_axes(masses, positions).0
+}
+
+/// Principal moments of inertia in ascending order and the corresponding
+/// unit axes.
+pub(crate) fn principal_axes(masses: &[f64], positions: &[f64]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mtot: f64 = masses.iter().sum();
+    let mut com = [0.0; 3];
+    for (m, r) in masses.iter().zip(positions.chunks_exact(3)) {
+        for x in 0..3 {
+            com[x] += m * r[x] / mtot;
+        }
+    }
+    let mut inertia = Matrix3::<f64>::zeros();
+    for (m, r) in masses.iter().zip(positions.chunks_exact(3)) {
+        let r = [r[0] - com[0], r[1] - com[1], r[2] - com[2]];
+        let r2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+        for i in 0..3 {
+            inertia[(i, i)] += m * r2;
+            for j in 0..3 {
+                inertia[(i, j)] -= m * r[i] * r[j];
+            }
+        }
+    }
+    let eigen = inertia.symmetric_eigen();
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| eigen.eigenvalues[a].total_cmp(&eigen.eigenvalues[b]));
+    let moments = order.map(|k| eigen.eigenvalues[k].max(0.0));
+    let axes = order.map(|k| {
+        let v = eigen.eigenvectors.column(k);
+        [v[0], v[1], v[2]]
+    });
+    (moments, axes)
+}
+
+/// Return rotational constants in cm^-1 in descending order. Zero moments
+/// of inertia give infinite constants.
+pub fn rotational_constants(masses: &[f64], positions: &[f64]) -> [f64; 3] {
+    moments_of_inertia(masses, positions).map(|i| PLANCK / (8.0 * PI * PI * SPEED_OF_LIGHT * i * AMU * BOHR * BOHR))
+}
+
+/// Return true if atoms at `positions` are (nearly) collinear, that is the
+/// smallest moment of inertia is below 1e-3 of the largest one. This is
+/// also used for projecting out rotations in vibrational analysis.
+pub fn is_linear(masses: &[f64], positions: &[f64]) -> bool {
+    let [ia, _, ic] = moments_of_inertia(masses, positions);
+    masses.len() > 1 && ia < 1e-3 * ic
+}
+// e5a34d18 ends here
+
+// [[file:../xtb.note::b0f7c369][b0f7c369]]
+/// Thermochemistry from harmonic frequencies. Entropies of low-frequency
+/// modes are interpolated with free rotor entropies in the modified rotor
+/// (quasi-RRHO) treatment of Grimme (Chem. Eur. J. 18, 9955 (2012)), as
+/// used in xtb.
+#[derive(Clone, Debug)]
+pub struct Thermochemistry {
+    temperature: f64,
+    pressure: f64,
+    symmetry_number: usize,
+    rotor_cutoff: f64,
+}
+
+impl Default for Thermochemistry {
+    fn default() -> Self {
+        Self {
+            temperature: 298.15,
+            pressure: 101325.0,
+            symmetry_number: 1,
+            rotor_cutoff: 50.0,
+        }
+    }
+}
+
+/// Contribution of one type of motion to the thermodynamic functions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThermoContribution {
+    /// Thermal enthalpy in Hartree, excluding zero point energy
+    pub enthalpy: f64,
+    /// Entropy in Hartree / K
+    pub entropy: f64,
+}
+
+/// Thermochemistry results. All energies are corrections to the electronic
+/// energy in Hartree.
+#[derive(Clone, Debug)]
+pub struct ThermoReport {
+    /// Temperature in K
+    pub temperature: f64,
+    /// Pressure in Pa
+    pub pressure: f64,
+    /// Rotational symmetry number
+    pub symmetry_number: usize,
+    /// Whether the molecule is linear
+    pub linear: bool,
+    /// Number of imaginary modes excluded from the vibrational analysis
+    pub n_imaginary: usize,
+    /// Zero point vibrational energy
+    pub zpe: f64,
+    /// Enthalpy correction H(T) - E including zero point energy and pV
+    pub enthalpy: f64,
+    /// Total entropy in Hartree / K
+    pub entropy: f64,
+    /// Gibbs free energy correction G(T) - E
+    pub gibbs: f64,
+    /// Contribution of translation
+    pub translational: ThermoContribution,
+    /// Contribution of rotation
+    pub rotational: ThermoContribution,
+    /// Contribution of vibration
+    pub vibrational: ThermoContribution,
+}
+
+impl ThermoReport {
+    /// Return total enthalpy for electronic `energy` in Hartree.
+    pub fn total_enthalpy(&self, energy: f64) -> f64 {
+        energy + self.enthalpy
+    }
+
+    /// Return total Gibbs free energy for electronic `energy` in Hartree.
+    pub fn total_free_energy(&self, energy: f64) -> f64 {
+        energy + self.gibbs
+    }
+}
+
+impl std::fmt::Display for ThermoReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // entropy in cal / (mol K)
+        let s = |x: f64| x * HARTREE * AVOGADRO / CALORIE;
+        writeln!(f, "temperature: {:.2} K, pressure: {:.1} Pa", self.temperature, self.pressure)?;
+        writeln!(f, "symmetry number: {}, linear: {}", self.symmetry_number, self.linear)?;
+        writeln!(f, "{:>8} {:>16} {:>16}", "", "H(T)/Eh", "S/cal/mol/K")?;
+        for (name, c) in [
+            ("TRANS", self.translational),
+            ("ROT", self.rotational),
+            ("VIB", self.vibrational),
+        ] {
+            writeln!(f, "{:>8} {:16.8} {:16.4}", name, c.enthalpy, s(c.entropy))?;
+        }
+        writeln!(f, "{:>8} {:16.8} {:16.4}", "TOT", self.enthalpy - self.zpe, s(self.entropy))?;
+        writeln!(f, "zero point energy: {:.8} Eh", self.zpe)?;
+        writeln!(f, "enthalpy correction: {:.8} Eh", self.enthalpy)?;
+        writeln!(f, "-T*S: {:.8} Eh", -self.temperature * self.entropy)?;
+        write!(f, "free energy correction: {:.8} Eh", self.gibbs)
+    }
+}
+
+impl Thermochemistry {
+    /// Set temperature in K. The default is 298.15 K.
+    pub fn temperature(&mut self, t: f64) -> &mut Self {
+        assert!(t > 0.0, "invalid temperature: {}", t);
+        self.temperature = t;
+        self
+    }
+
+    /// Set pressure in Pa. The default is 1 atm.
+    pub fn pressure(&mut self, p: f64) -> &mut Self {
+        assert!(p > 0.0, "invalid pressure: {}", p);
+        self.pressure = p;
+        self
+    }
+
+    /// Set rotational symmetry number, for example 2 for water and 12 for
+    /// methane. The default is 1.
+    pub fn symmetry_number(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid symmetry number: {}", n);
+        self.symmetry_number = n;
+        self
+    }
+
+    /// Set frequency in cm^-1 for switching between harmonic oscillator and
+    /// free rotor entropies. The default is 50 cm^-1, the same as xtb.
+    pub fn rotor_cutoff(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid rotor cutoff: {}", x);
+        self.rotor_cutoff = x;
+        self
+    }
+
+    /// Compute thermochemistry for a molecule with atomic `masses` in amu
+    /// at `positions` in Bohr, from its normal `modes`. Imaginary
+    /// frequencies within the cutoff of `modes` are treated as real ones,
+    /// and larger ones are excluded.
+    pub fn compute(&self, masses: &[f64], positions: &[f64], modes: &NormalModes) -> Result<ThermoReport> {
+        ensure!(positions.len() == 3 * masses.len(), "positions do not match {} atoms", masses.len());
+        let t = self.temperature;
+        let kt = BOLTZMANN * t;
+
+        // translation
+        let mtot = masses.iter().sum::<f64>() * AMU;
+        let q_trans = (2.0 * PI * mtot * kt / (PLANCK * PLANCK)).powf(1.5) * kt / self.pressure;
+        let translational = ThermoContribution {
+            enthalpy: 2.5 * kt,
+            entropy: BOLTZMANN * (q_trans.ln() + 2.5),
+        };
+
+        // rotation
+        let linear = modes.linear;
+        let theta = moments_of_inertia(masses, positions)
+            .map(|i| PLANCK * PLANCK / (8.0 * PI * PI * i * AMU * BOHR * BOHR * BOLTZMANN));
+        let sigma = self.symmetry_number as f64;
+        let rotational = if masses.len() == 1 {
+            ThermoContribution::default()
+        } else if linear {
+            let q_rot = t / (sigma * theta[2]);
+            ThermoContribution {
+                enthalpy: kt,
+                entropy: BOLTZMANN * (q_rot.ln() + 1.0),
+            }
+        } else {
+            let q_rot = PI.sqrt() / sigma * (t.powi(3) / (theta[0] * theta[1] * theta[2])).sqrt();
+            ThermoContribution {
+                enthalpy: 1.5 * kt,
+                entropy: BOLTZMANN * (q_rot.ln() + 1.5),
+            }
+        };
+
+        // vibration
+        let mut zpe = 0.0;
+        let mut vibrational = ThermoContribution::default();
+        let mut n_imaginary = 0;
+        // average moment of inertia for free rotors in kg m^2
+        let b_av = 1e-44;
+        for &freq in modes.frequencies.iter() {
+            if freq < -modes.imaginary_cutoff {
+                n_imaginary += 1;
+                continue;
+            }
+            let freq = freq.abs();
+            if freq < 1e-3 {
+                continue;
+            }
+            let e = PLANCK * SPEED_OF_LIGHT * freq;
+            let x = e / kt;
+            zpe += 0.5 * e;
+            vibrational.enthalpy += e / x.exp_m1();
+            let s_vib = BOLTZMANN * (x / x.exp_m1() - (-(-x).exp_m1()).ln());
+            let mu = PLANCK / (8.0 * PI * PI * SPEED_OF_LIGHT * freq);
+            let mu = mu * b_av / (mu + b_av);
+            let s_rot = BOLTZMANN * (0.5 + (8.0 * PI.powi(3) * mu * kt / (PLANCK * PLANCK)).sqrt().ln());
+            let w = 1.0 / (1.0 + (self.rotor_cutoff / freq).powi(4));
+            vibrational.entropy += w * s_vib + (1.0 - w) * s_rot;
+        }
+
+        let to_au = |c: ThermoContribution| ThermoContribution {
+            enthalpy: c.enthalpy / HARTREE,
+            entropy: c.entropy / HARTREE,
+        };
+        let translational = to_au(translational);
+        let rotational = to_au(rotational);
+        let vibrational = to_au(vibrational);
+        let zpe = zpe / HARTREE;
+        let enthalpy = zpe + translational.enthalpy + rotational.enthalpy + vibrational.enthalpy;
+        let entropy = translational.entropy + rotational.entropy + vibrational.entropy;
+        let report = ThermoReport {
+            temperature: t,
+            pressure: self.pressure,
+            symmetry_number: self.symmetry_number,
+            linear,
+            n_imaginary,
+            zpe,
+            enthalpy,
+            entropy,
+            gibbs: enthalpy - t * entropy,
+            translational,
+            rotational,
+            vibrational,
+        };
+        Ok(report)
+    }
+}
+// b0f7c369 ends here
@@ -0,0 +1,741 @@
+// [[file:../xtb.note::d2a7c3e1][d2a7c3e1]]
+//! Transition state search with the dimer method and partitioned rational
+//! function optimization (P-RFO)
+// d2a7c3e1 ends here
+
+// [[file:../xtb.note::64e1b0f8][64e1b0f8]]
+use crate::hessian::{translations_rotations, NormalModes, NumericalHessian, VibrationalAnalysis};
+use crate::opt::{limit_step, Lbfgs, LbfgsStepper, OptConvergence, OptProgress, OptTermination, Stepper};
+use crate::potential::Potential;
+use crate::utils::*;
+
+use anyhow::*;
+use nalgebra::{DMatrix, DVector};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, StandardNormal};
+// 64e1b0f8 ends here
+
+// [[file:../xtb.note::9f3c5a72][9f3c5a72]]
+/// Final report of transition state search.
+#[derive(Clone, Debug)]
+pub struct TsReport {
+    /// Final energy in Hartree
+    pub energy: f64,
+    /// Final positions in Bohr
+    pub positions: Vec<f64>,
+    /// Final gradient in Hartree / Bohr
+    pub gradient: Vec<f64>,
+    /// Number of optimization steps taken
+    pub n_steps: usize,
+    /// Number of energy and gradient evaluations, excluding those for
+    /// verification
+    pub n_evaluations: usize,
+    /// Reason of termination
+    pub termination: OptTermination,
+    /// Normal modes at the final structure from numerical Hessian, if
+    /// verification is enabled
+    pub modes: Option<NormalModes>,
+}
+
+impl TsReport {
+    /// Return true if the search converged.
+    pub fn converged(&self) -> bool {
+        self.termination == OptTermination::Converged
+    }
+
+    /// Return the imaginary frequency in cm^-1 as a negative number, if
+    /// there is exactly one beyond the imaginary cutoff.
+    pub fn imaginary_frequency(&self) -> Option<f64> {
+        let modes = self.modes.as_ref()?;
+        (modes.n_imaginary() == 1).then(|| modes.frequencies[0])
+    }
+
+    /// Return true if the search converged to a first order saddle point,
+    /// verified by exactly one imaginary frequency.
+    pub fn is_transition_state(&self) -> bool {
+        self.converged() && self.imaginary_frequency().is_some()
+    }
+}
+
+/// Core of a transition state search algorithm, which could evaluate the
+/// potential for extra information, for example the curvature.
+trait TsStepper {
+    /// Prepare for search starting from `positions`.
+    fn init<P: Potential + ?Sized>(&mut self, pot: &mut P, positions: &[f64], n_evaluations: &mut usize) -> Result<()>;
+
+    /// Propose displacement from current `positions` and `gradient`.
+    fn propose<P: Potential + ?Sized>(
+        &mut self,
+        pot: &mut P,
+        positions: &[f64],
+        gradient: &[f64],
+        max_step: f64,
+        n_evaluations: &mut usize,
+    ) -> Result<Vec<f64>>;
+
+    /// Update internal state after step `s` with `energy_change`, and new
+    /// `gradient`.
+    fn update(&mut self, s: &[f64], energy_change: f64, gradient: &[f64]);
+
+    /// Return true if the curvature at `positions` is that of a first order
+    /// saddle point. Convergence is reported only when this holds.
+    fn saddle_point<P: Potential + ?Sized>(
+        &mut self,
+        pot: &mut P,
+        positions: &[f64],
+        gradient: &[f64],
+        n_evaluations: &mut usize,
+    ) -> Result<bool>;
+}
+
+/// Common settings of transition state search driver.
+#[derive(Clone, Debug)]
+struct TsDriver {
+    convergence: OptConvergence,
+    max_steps: usize,
+    max_step: f64,
+    verify: bool,
+    hessian: NumericalHessian,
+    vibrations: VibrationalAnalysis,
+}
+
+impl TsDriver {
+    fn new(atom_types: &[i32]) -> Result<Self> {
+        let driver = Self {
+            convergence: OptConvergence::default(),
+            max_steps: 200,
+            max_step: 0.3,
+            verify: true,
+            hessian: NumericalHessian::default(),
+            vibrations: VibrationalAnalysis::new(atom_types)?,
+        };
+        Ok(driver)
+    }
+
+    fn run<P, S, F>(&self, pot: &mut P, positions: &[f64], stepper: &mut S, mut callback: F) -> Result<TsReport>
+    where
+        P: Potential + ?Sized,
+        S: TsStepper,
+        F: FnMut(&OptProgress) -> bool,
+    {
+        let natoms = self.vibrations.masses().len();
+        ensure!(positions.len() == 3 * natoms, "positions do not match {} atoms", natoms);
+        let n = positions.len();
+
+        let mut x = positions.to_vec();
+        let mut g = vec![0.0; n];
+        let mut e = pot.evaluate(&x, &mut g)?;
+        let mut n_evaluations = 1;
+        stepper.init(pot, &x, &mut n_evaluations)?;
+
+        let mut g_new = vec![0.0; n];
+        let mut step = 0;
+        let mut energy_change = 0.0;
+        let mut max_displacement = 0.0;
+        let termination = loop {
+            let progress = OptProgress {
+                step,
+                n_evaluations,
+                energy: e,
+                energy_change,
+                max_gradient: max_abs(&g),
+                rms_gradient: rms(&g),
+                max_displacement,
+                positions: &x,
+                gradient: &g,
+            };
+            if !callback(&progress) {
+                break OptTermination::Stopped;
+            }
+            // a stationary point is not enough, e.g. when starting from a minimum
+            if self.convergence.is_converged(&progress) && stepper.saddle_point(pot, &x, &g, &mut n_evaluations)? {
+                break OptTermination::Converged;
+            }
+            if step >= self.max_steps {
+                break OptTermination::MaxStepsReached;
+            }
+
+            step += 1;
+            let mut d = stepper.propose(pot, &x, &g, self.max_step, &mut n_evaluations)?;
+            limit_step(&mut d, self.max_step);
+            x.iter_mut().zip(&d).for_each(|(xi, di)| *xi += di);
+            let e_new = pot.evaluate(&x, &mut g_new)?;
+            n_evaluations += 1;
+            stepper.update(&d, e_new - e, &g_new);
+            energy_change = e_new - e;
+            max_displacement = max_atom_norm(&d);
+            e = e_new;
+            g.clone_from(&g_new);
+        };
+
+        let modes = if self.verify {
+            let hessian = self.hessian.compute(pot, &x)?;
+            Some(self.vibrations.normal_modes(&x, &hessian)?)
+        } else {
+            None
+        };
+        let report = TsReport {
+            energy: e,
+            positions: x,
+            gradient: g,
+            n_steps: step,
+            n_evaluations,
+            termination,
+            modes,
+        };
+        Ok(report)
+    }
+}
+
+/// Orthonormal basis vectors orthogonal to overall translations and
+/// rotations at `positions`.
+fn internal_basis(positions: &[f64]) -> DMatrix<f64> {
+    let n = positions.len();
+    let (tr, _) = translations_rotations(&vec![1.0; n / 3], positions);
+    let mut projector = DMatrix::<f64>::identity(n, n);
+    for v in tr.iter() {
+        projector -= v * v.transpose();
+    }
+    let eigen = projector.symmetric_eigen();
+    let columns: Vec<_> = (0..n)
+        .filter(|&i| eigen.eigenvalues[i] > 0.5)
+        .map(|i| eigen.eigenvectors.column(i).into_owned())
+        .collect();
+    DMatrix::from_columns(&columns)
+}
+
+/// Remove overall translations and rotations from `v` and normalize it.
+fn project_internal(positions: &[f64], v: &mut [f64]) -> Result<()> {
+    let (tr, _) = translations_rotations(&vec![1.0; positions.len() / 3], positions);
+    for t in tr.iter() {
+        let c: f64 = t.iter().zip(v.iter()).map(|(a, b)| a * b).sum();
+        v.iter_mut().zip(t.iter()).for_each(|(vi, ti)| *vi -= c * ti);
+    }
+    let vnorm = norm(v);
+    ensure!(vnorm > 1e-8, "direction has no internal component");
+    v.iter_mut().for_each(|x| *x /= vnorm);
+    Ok(())
+}
+
+macro_rules! impl_ts_driver_settings {
+    ($ts:ident) => {
+        impl $ts {
+            /// Set convergence criteria.
+            pub fn convergence(&mut self, convergence: OptConvergence) -> &mut Self {
+                self.driver.convergence = convergence;
+                self
+            }
+
+            /// Set maximum number of steps. The default is 200.
+            pub fn max_steps(&mut self, n: usize) -> &mut Self {
+                self.driver.max_steps = n;
+                self
+            }
+
+            /// Set maximum displacement of any atom in a single step in Bohr.
+            pub fn max_step_size(&mut self, x: f64) -> &mut Self {
+                assert!(x > 0.0, "invalid step size: {}", x);
+                self.driver.max_step = x;
+                self
+            }
+
+            /// Enable or disable verification of the final structure by
+            /// harmonic frequencies from numerical Hessian. The default is
+            /// enabled.
+            pub fn verify(&mut self, verify: bool) -> &mut Self {
+                self.driver.verify = verify;
+                self
+            }
+
+            /// Set displacement step in Bohr for numerical Hessian.
+            pub fn hessian_step_size(&mut self, x: f64) -> &mut Self {
+                self.driver.hessian.step_size(x);
+                self
+            }
+
+            /// Set threshold in cm^-1 below which imaginary frequencies in
+            /// verification are regarded as numerical noise. The default is
+            /// 20 cm^-1.
+            pub fn imaginary_cutoff(&mut self, x: f64) -> &mut Self {
+                self.driver.vibrations.imaginary_cutoff(x);
+                self
+            }
+
+            /// Search transition state of `pot` starting from `positions`
+            /// in Bohr.
+            pub fn search<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64]) -> Result<TsReport> {
+                self.search_with(pot, positions, |_| true)
+            }
+
+            /// Search transition state of `pot` starting from `positions`
+            /// in Bohr. `callback` is called for each step with current
+            /// progress, and the search will be stopped if it returns
+            /// false.
+            pub fn search_with<P, F>(&self, pot: &mut P, positions: &[f64], callback: F) -> Result<TsReport>
+            where
+                P: Potential + ?Sized,
+                F: FnMut(&OptProgress) -> bool,
+            {
+                let mut stepper = self.stepper();
+                self.driver.run(pot, positions, &mut stepper, callback)
+            }
+        }
+    };
+}
+// 9f3c5a72 ends here
+
+// [[file:../xtb.note::4b8e2f06][4b8e2f06]]
+/// Dimer method of Henkelman and Jónsson (J. Chem. Phys. 111, 7010 (1999))
+/// requiring only gradients. The dimer is rotated with the curvature
+/// interpolation of Kästner and Sherwood (J. Chem. Phys. 128, 014106
+/// (2008)), and translated with L-BFGS.
+#[derive(Clone, Debug)]
+pub struct Dimer {
+    driver: TsDriver,
+    separation: f64,
+    max_rotations: usize,
+    direction: Option<Vec<f64>>,
+    seed: u64,
+}
+
+impl_ts_driver_settings!(Dimer);
+
+impl Dimer {
+    /// Create dimer search for molecule with `atom_types` in atomic
+    /// numbers.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        let dimer = Self {
+            driver: TsDriver::new(atom_types)?,
+            separation: 0.01,
+            max_rotations: 4,
+            direction: None,
+            seed: 0,
+        };
+        Ok(dimer)
+    }
+
+    /// Set distance in Bohr between center and endpoint of the dimer. The
+    /// default is 0.01 Bohr.
+    pub fn separation(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid separation: {}", x);
+        self.separation = x;
+        self
+    }
+
+    /// Set maximum number of rotations before each translation. The
+    /// default is 4.
+    pub fn max_rotations(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "invalid number of rotations: {}", n);
+        self.max_rotations = n;
+        self
+    }
+
+    /// Set initial orientation of the dimer, which should be a guess of
+    /// the reaction coordinate. A random direction is used by default.
+    pub fn direction(&mut self, v: &[f64]) -> &mut Self {
+        self.direction = Some(v.to_vec());
+        self
+    }
+
+    /// Set seed of random initial orientation.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    fn stepper(&self) -> DimerStepper {
+        DimerStepper {
+            params: self.clone(),
+            direction: vec![],
+            curvature: 0.0,
+            translation: Lbfgs::default().stepper(),
+            last: None,
+        }
+    }
+}
+
+struct DimerStepper {
+    params: Dimer,
+    // unit vector along the dimer
+    direction: Vec<f64>,
+    curvature: f64,
+    translation: LbfgsStepper,
+    // last translation and effective gradient
+    last: Option<(Vec<f64>, Vec<f64>)>,
+}
+
+/// Stop rotations below this angle in radians.
+const DIMER_ANGLE_TOLERANCE: f64 = 1e-2;
+
+impl DimerStepper {
+    /// Gradient at endpoint of the dimer along `direction`.
+    fn endpoint_gradient<P: Potential + ?Sized>(&self, pot: &mut P, positions: &[f64], direction: &[f64]) -> Result<Vec<f64>> {
+        let dr = self.params.separation;
+        let x: Vec<_> = positions.iter().zip(direction).map(|(x, n)| x + dr * n).collect();
+        let mut g1 = vec![0.0; x.len()];
+        pot.evaluate(&x, &mut g1)?;
+        Ok(g1)
+    }
+
+    /// Rotate the dimer toward the lowest curvature mode, with conjugate
+    /// gradient search directions.
+    fn rotate<P: Potential + ?Sized>(&mut self, pot: &mut P, positions: &[f64], gradient: &[f64], n_evaluations: &mut usize) -> Result<()> {
+        let dr = self.params.separation;
+        // rotational force and search direction of previous rotation
+        let mut last: Option<(Vec<f64>, Vec<f64>)> = None;
+        for _ in 0..self.params.max_rotations {
+            let g1 = self.endpoint_gradient(pot, positions, &self.direction)?;
+            *n_evaluations += 1;
+            let dg: Vec<_> = g1.iter().zip(gradient).map(|(a, b)| (a - b) / dr).collect();
+            let c0 = dot(&dg, &self.direction);
+            self.curvature = c0;
+
+            // rotating along the perpendicular component of the gradient
+            // difference lowers the curvature
+            let force: Vec<_> = dg.iter().zip(&self.direction).map(|(d, n)| -(d - c0 * n)).collect();
+            if norm(&force) < 1e-8 {
+                break;
+            }
+            let mut search = force.clone();
+            if let Some((f_last, s_last)) = &last {
+                let f2 = dot(f_last, f_last);
+                let gamma = (dot(&force, &force) - dot(&force, f_last)) / f2;
+                if gamma > 0.0 {
+                    search.iter_mut().zip(s_last).for_each(|(s, sl)| *s += gamma * sl);
+                }
+            }
+            // keep search direction perpendicular to the dimer
+            let sn = dot(&search, &self.direction);
+            search.iter_mut().zip(&self.direction).for_each(|(s, n)| *s -= sn * n);
+            let snorm = norm(&search);
+            let theta: Vec<_> = search.iter().map(|s| s / snorm).collect();
+
+            // curvature C(t) = a0/2 + a1 cos(2t) + b1 sin(2t) in the
+            // rotation plane, fitted from a trial rotation
+            let dc0 = -2.0 * dot(&force, &theta);
+            if dc0 >= 0.0 {
+                break;
+            }
+            let theta1 = 0.5 * (-dc0 / (2.0 * c0.abs())).atan();
+            if theta1 < DIMER_ANGLE_TOLERANCE {
+                break;
+            }
+            let rotated = |t: f64| -> Vec<f64> { self.direction.iter().zip(&theta).map(|(n, th)| n * t.cos() + th * t.sin()).collect() };
+            let n1 = rotated(theta1);
+            let g1 = self.endpoint_gradient(pot, positions, &n1)?;
+            *n_evaluations += 1;
+            let c1 = g1.iter().zip(gradient).zip(&n1).map(|((a, b), n)| (a - b) * n).sum::<f64>() / dr;
+            let b1 = 0.5 * dc0;
+            let a1 = (c0 - c1 + b1 * (2.0 * theta1).sin()) / (1.0 - (2.0 * theta1).cos());
+            let a0 = 2.0 * (c0 - a1);
+            let curvature = |t: f64| 0.5 * a0 + a1 * (2.0 * t).cos() + b1 * (2.0 * t).sin();
+            let mut theta_min = 0.5 * (b1 / a1).atan();
+            if curvature(theta_min) > curvature(theta_min + 0.5 * std::f64::consts::PI) {
+                theta_min += 0.5 * std::f64::consts::PI;
+            }
+
+            let mut direction = rotated(theta_min);
+            project_internal(positions, &mut direction)?;
+            // search direction transported into the new rotation plane
+            let (sin, cos) = theta_min.sin_cos();
+            let s_last = self.direction.iter().zip(&theta).map(|(n, th)| snorm * (th * cos - n * sin)).collect();
+            last = Some((force, s_last));
+            self.direction = direction;
+            self.curvature = curvature(theta_min);
+            if theta_min.abs() < DIMER_ANGLE_TOLERANCE {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TsStepper for DimerStepper {
+    fn init<P: Potential + ?Sized>(&mut self, _pot: &mut P, positions: &[f64], _n_evaluations: &mut usize) -> Result<()> {
+        let n = positions.len();
+        let mut direction = match &self.params.direction {
+            Some(v) => {
+                ensure!(v.len() == n, "invalid size of dimer direction: {}", v.len());
+                v.clone()
+            }
+            None => {
+                let mut rng = StdRng::seed_from_u64(self.params.seed);
+                (0..n).map(|_| StandardNormal.sample(&mut rng)).collect()
+            }
+        };
+        project_internal(positions, &mut direction)?;
+        self.direction = direction;
+        Ok(())
+    }
+
+    fn propose<P: Potential + ?Sized>(
+        &mut self,
+        pot: &mut P,
+        positions: &[f64],
+        gradient: &[f64],
+        max_step: f64,
+        n_evaluations: &mut usize,
+    ) -> Result<Vec<f64>> {
+        let negative = self.curvature < 0.0;
+        self.rotate(pot, positions, gradient, n_evaluations)?;
+
+        // restart L-BFGS when the character of the surface changes
+        if negative != (self.curvature < 0.0) {
+            self.translation = Lbfgs::default().stepper();
+            self.last = None;
+        }
+
+        // in convex regions, move uphill along the dimer by the maximum
+        // step, which also escapes from minima where the gradient vanishes
+        let gn = dot(gradient, &self.direction);
+        if self.curvature >= 0.0 {
+            let sign = if gn < 0.0 { -1.0 } else { 1.0 };
+            let mut d: Vec<_> = self.direction.iter().map(|n| sign * n).collect();
+            let dmax = max_atom_norm(&d);
+            d.iter_mut().for_each(|x| *x *= max_step / dmax);
+            return Ok(d);
+        }
+
+        // invert the gradient component along the dimer
+        let g_eff: Vec<_> = gradient.iter().zip(&self.direction).map(|(g, n)| g - 2.0 * gn * n).collect();
+        if let Some((s, g_last)) = self.last.take() {
+            let y: Vec<_> = g_eff.iter().zip(&g_last).map(|(a, b)| a - b).collect();
+            self.translation.update(&s, &y);
+        }
+        let mut d = self.translation.propose(positions, &g_eff, max_step)?;
+        limit_step(&mut d, max_step);
+        self.last = Some((d.clone(), g_eff));
+        Ok(d)
+    }
+
+    fn update(&mut self, _s: &[f64], _energy_change: f64, _gradient: &[f64]) {}
+
+    fn saddle_point<P: Potential + ?Sized>(
+        &mut self,
+        pot: &mut P,
+        positions: &[f64],
+        gradient: &[f64],
+        n_evaluations: &mut usize,
+    ) -> Result<bool> {
+        self.rotate(pot, positions, gradient, n_evaluations)?;
+        Ok(self.curvature < 0.0)
+    }
+}
+// 4b8e2f06 ends here
+
+// [[file:../xtb.note::e7d0a94c][e7d0a94c]]
+/// Partitioned rational function optimization (P-RFO) of Baker (J. Comput.
+/// Chem. 7, 385 (1986)) in Cartesian coordinates, maximizing energy along
+/// one Hessian eigenvector and minimizing along all others. The Hessian is
+/// computed numerically at the start and updated with the Bofill formula.
+#[derive(Clone, Debug)]
+pub struct Prfo {
+    driver: TsDriver,
+    follow_mode: usize,
+    recompute_hessian: usize,
+    trust_radius: f64,
+}
+
+impl_ts_driver_settings!(Prfo);
+
+impl Prfo {
+    /// Create P-RFO search for molecule with `atom_types` in atomic
+    /// numbers.
+    pub fn new(atom_types: &[i32]) -> Result<Self> {
+        let prfo = Self {
+            driver: TsDriver::new(atom_types)?,
+            follow_mode: 0,
+            recompute_hessian: 0,
+            trust_radius: 0.1,
+        };
+        Ok(prfo)
+    }
+
+    /// Follow the Hessian eigenvector with index `i` in ascending order of
+    /// eigenvalues at the starting structure. The default is 0 for the
+    /// lowest mode.
+    pub fn follow_mode(&mut self, i: usize) -> &mut Self {
+        self.follow_mode = i;
+        self
+    }
+
+    /// Recompute numerical Hessian every `n` steps instead of updating it.
+    /// The default is 0 for no recomputation.
+    pub fn recompute_hessian(&mut self, n: usize) -> &mut Self {
+        self.recompute_hessian = n;
+        self
+    }
+
+    /// Set initial trust radius as the largest atomic displacement in Bohr.
+    /// The default is 0.1 Bohr.
+    pub fn trust_radius(&mut self, x: f64) -> &mut Self {
+        assert!(x > 0.0, "invalid trust radius: {}", x);
+        self.trust_radius = x;
+        self
+    }
+
+    fn stepper(&self) -> PrfoStepper {
+        PrfoStepper {
+            params: self.clone(),
+            hessian: DMatrix::zeros(0, 0),
+            mode: None,
+            trust: self.trust_radius,
+            step: 0,
+            g: vec![],
+            predicted: 0.0,
+            step_norm: 0.0,
+        }
+    }
+}
+
+struct PrfoStepper {
+    params: Prfo,
+    // Cartesian Hessian
+    hessian: DMatrix<f64>,
+    // followed mode in Cartesian coordinates
+    mode: Option<DVector<f64>>,
+    trust: f64,
+    step: usize,
+    // gradient, predicted energy change and length of last step
+    g: Vec<f64>,
+    predicted: f64,
+    step_norm: f64,
+}
+
+impl PrfoStepper {
+    fn compute_hessian<P: Potential + ?Sized>(&mut self, pot: &mut P, positions: &[f64], n_evaluations: &mut usize) -> Result<()> {
+        let n = positions.len();
+        let h = self.params.driver.hessian.compute(pot, positions)?;
+        *n_evaluations += 2 * n;
+        self.hessian = DMatrix::from_row_slice(n, n, &h);
+        Ok(())
+    }
+}
+
+impl TsStepper for PrfoStepper {
+    fn init<P: Potential + ?Sized>(&mut self, pot: &mut P, positions: &[f64], n_evaluations: &mut usize) -> Result<()> {
+        self.compute_hessian(pot, positions, n_evaluations)
+    }
+
+    fn propose<P: Potential + ?Sized>(
+        &mut self,
+        pot: &mut P,
+        positions: &[f64],
+        gradient: &[f64],
+        max_step: f64,
+        n_evaluations: &mut usize,
+    ) -> Result<Vec<f64>> {
+        let nrecomp = self.params.recompute_hessian;
+        if nrecomp > 0 && self.step > 0 && self.step % nrecomp == 0 {
+            self.compute_hessian(pot, positions, n_evaluations)?;
+        }
+        self.step += 1;
+
+        // eigenmodes of Hessian with translations and rotations removed
+        let basis = internal_basis(positions);
+        let h = basis.transpose() * &self.hessian * &basis;
+        let gq = basis.transpose() * DVector::from_column_slice(gradient);
+        let eigen = h.symmetric_eigen();
+        let m = eigen.eigenvalues.len();
+        ensure!(m > 0, "no internal degrees of freedom");
+        let mut order: Vec<_> = (0..m).collect();
+        order.sort_by(|&a, &b| eigen.eigenvalues[a].total_cmp(&eigen.eigenvalues[b]));
+        let modes: Vec<DVector<f64>> = (0..m).map(|i| &basis * eigen.eigenvectors.column(i)).collect();
+
+        // follow the mode with the largest overlap to the previous one
+        let k = match &self.mode {
+            None => order[self.params.follow_mode.min(m - 1)],
+            Some(v) => (0..m)
+                .max_by(|&a, &b| modes[a].dot(v).abs().total_cmp(&modes[b].dot(v).abs()))
+                .expect("no modes"),
+        };
+        self.mode = Some(modes[k].clone());
+
+        let b = &eigen.eigenvalues;
+        let f: Vec<_> = (0..m).map(|i| eigen.eigenvectors.column(i).dot(&gq)).collect();
+        // maximize along mode k
+        let lambda_p = 0.5 * (b[k] + (b[k] * b[k] + 4.0 * f[k] * f[k]).sqrt());
+        // minimize along the others from the lowest eigenvalue of the
+        // augmented Hessian
+        let others: Vec<_> = (0..m).filter(|&i| i != k).collect();
+        let no = others.len();
+        let mut aug = DMatrix::zeros(no + 1, no + 1);
+        for (a, &i) in others.iter().enumerate() {
+            aug[(a, a)] = b[i];
+            aug[(a, no)] = f[i];
+            aug[(no, a)] = f[i];
+        }
+        let lambda_n = aug.symmetric_eigenvalues().min();
+
+        let mut step = DVector::zeros(positions.len());
+        for i in 0..m {
+            let lambda = if i == k { lambda_p } else { lambda_n };
+            let denom = b[i] - lambda;
+            if denom.abs() > 1e-10 {
+                step -= &modes[i] * (f[i] / denom);
+            } else if i == k {
+                // no gradient along a positive mode, e.g. at a minimum:
+                // move uphill by the trust radius
+                step += &modes[i] * (self.trust / max_atom_norm(modes[i].as_slice()));
+            }
+        }
+        let mut d: Vec<_> = step.iter().copied().collect();
+        self.trust = self.trust.min(max_step);
+        limit_step(&mut d, self.trust);
+
+        let s = DVector::from_column_slice(&d);
+        self.predicted = dot(gradient, &d) + 0.5 * s.dot(&(&self.hessian * &s));
+        self.step_norm = max_atom_norm(&d);
+        self.g = gradient.to_vec();
+        Ok(d)
+    }
+
+    fn update(&mut self, s: &[f64], energy_change: f64, gradient: &[f64]) {
+        // trust radius from agreement with the quadratic model
+        let ratio = energy_change / self.predicted;
+        if (0.75..=1.25).contains(&ratio) && self.step_norm > 0.99 * self.trust {
+            self.trust = (1.5 * self.trust).min(self.params.driver.max_step);
+        } else if !(0.25..=1.75).contains(&ratio) {
+            self.trust = (0.5 * self.trust).max(1e-3);
+        }
+
+        let y: Vec<_> = gradient.iter().zip(&self.g).map(|(a, b)| a - b).collect();
+        bofill_update(&mut self.hessian, s, &y);
+    }
+
+    fn saddle_point<P: Potential + ?Sized>(
+        &mut self,
+        _pot: &mut P,
+        positions: &[f64],
+        _gradient: &[f64],
+        _n_evaluations: &mut usize,
+    ) -> Result<bool> {
+        // exactly one negative eigenvalue of the current Hessian
+        let basis = internal_basis(positions);
+        let h = basis.transpose() * &self.hessian * &basis;
+        let n_negative = h.symmetric_eigenvalues().iter().filter(|&&x| x < 0.0).count();
+        Ok(n_negative == 1)
+    }
+}
+
+/// Update `hessian` with the Bofill formula for step `s` and gradient
+/// change `y`, suitable for Hessians with negative eigenvalues.
+pub(crate) fn bofill_update(hessian: &mut DMatrix<f64>, s: &[f64], y: &[f64]) {
+    let s = DVector::from_column_slice(s);
+    let xi = DVector::from_column_slice(y) - &*hessian * &s;
+    let ss = s.dot(&s);
+    let xs = xi.dot(&s);
+    let xx = xi.dot(&xi);
+    if ss < 1e-12 || xx < 1e-20 {
+        return;
+    }
+    // weighting between Murtagh-Sargent and Powell-symmetric-Broyden updates
+    let phi = xs * xs / (xx * ss);
+    let psb = (&xi * s.transpose() + &s * xi.transpose()) / ss - &s * s.transpose() * (xs / (ss * ss));
+    *hessian += psb * (1.0 - phi);
+    if xs.abs() > 1e-12 {
+        *hessian += &xi * xi.transpose() * (phi / xs);
+    }
+}
+// e7d0a94c ends here
@@ -0,0 +1,34 @@
+// [[file:../xtb.note::0e4d7a91][0e4d7a91]]
+//! Helper functions for vectors stored in flat slices
+// 0e4d7a91 ends here
+
+// [[file:../xtb.note::f3b8c605][f3b8c605]]
+/// Dot product of two vectors.
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Euclidean norm of vector `a`.
+pub(crate) fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Largest absolute component of vector `a`.
+pub(crate) fn max_abs(a: &[f64]) -> f64 {
+    a.iter().fold(0.0, |m, x| x.abs().max(m))
+}
+
+/// Root mean square of vector `a`.
+pub(crate) fn rms(a: &[f64]) -> f64 {
+    if a.is_empty() {
+        0.0
+    } else {
+        (dot(a, a) / a.len() as f64).sqrt()
+    }
+}
+
+/// Largest atomic displacement in Cartesian displacement vector `d`.
+pub(crate) fn max_atom_norm(d: &[f64]) -> f64 {
+    d.chunks(3).map(norm).fold(0.0, f64::max)
+}
+// f3b8c605 ends here
@@ -6,6 +6,9 @@
 use super::*;
 
 use libxtb::*;
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 // a7b88800 ends here
 
 // [[file:../xtb.note::392dc74e][392dc74e]]
@@ -15,12 +18,33 @@ pub struct XtbParameters {
     uhf: usize,
     charge: f64,
     verbosity: XtbOutputVerbosity,
+    output: XtbOutputTarget,
     max_iterations: usize,
     electronic_temperature: f64,
     method: XtbMethod,
+    parameter_file: Option<PathBuf>,
     lattice: Option<[f64; 9]>,
     periodic: [bool; 3],
-    // TODO: solvent
+    solvation: XtbSolvation,
+    properties: XtbProperties,
+    history: usize,
+}
+
+/// Optional properties to be collected after evaluation.
+#[derive(Clone, Debug, Default)]
+struct XtbProperties {
+    bond_orders: bool,
+    orbital_energies: bool,
+    orbital_coefficients: bool,
+}
+
+/// Implicit solvation settings applied after loading parametrization.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct XtbSolvation {
+    solvent: Option<String>,
+    state: XtbSolutionState,
+    temperature: Option<f64>,
+    grid: Option<usize>,
 }
 
 #[derive(Clone, Debug)]
@@ -30,6 +54,14 @@ pub enum XtbOutputVerbosity {
     Verbose,
 }
 
+/// Where to write the calculation output.
+#[derive(Clone, Debug, PartialEq)]
+enum XtbOutputTarget {
+    Stdout,
+    File(PathBuf),
+    Capture,
+}
+
 impl From<&str> for XtbMethod {
     fn from(s: &str) -> Self {
         match s.to_uppercase().as_str() {
@@ -48,11 +80,16 @@ impl Default for XtbParameters {
             uhf: 0,
             charge: 0.0,
             verbosity: XtbOutputVerbosity::Muted,
+            output: XtbOutputTarget::Stdout,
             max_iterations: 250,
             electronic_temperature: 300.0,
             method: XtbMethod::GFN2xTB,
+            parameter_file: None,
             lattice: None,
             periodic: [false; 3],
+            solvation: XtbSolvation::default(),
+            properties: XtbProperties::default(),
+            history: 0,
         }
     }
 }
@@ -101,21 +138,127 @@ impl XtbParameters {
         self
     }
 
+    /// Write calculation output into file `path` instead of stdout. The file
+    /// will be kept open during the lifetime of `XtbModel`.
+    pub fn output_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.output = XtbOutputTarget::File(path.as_ref().to_owned());
+        self
+    }
+
+    /// Capture calculation output of each evaluation into a `String`, which
+    /// can be retrieved by `XtbModel::get_output`. Output from loading
+    /// parametrization is captured as well, so nothing is written to
+    /// stdout. Note that the output verbosity still applies.
+    pub fn capture_output(&mut self) -> &mut Self {
+        self.output = XtbOutputTarget::Capture;
+        self
+    }
+
+    /// Collect Wiberg bond orders after evaluation.
+    pub fn bond_orders(&mut self, collect: bool) -> &mut Self {
+        self.properties.bond_orders = collect;
+        self
+    }
+
+    /// Collect orbital energies and occupation numbers after evaluation.
+    pub fn orbital_energies(&mut self, collect: bool) -> &mut Self {
+        self.properties.orbital_energies = collect;
+        self
+    }
+
+    /// Collect orbital coefficients after evaluation, which could be
+    /// expensive for large system.
+    pub fn orbital_coefficients(&mut self, collect: bool) -> &mut Self {
+        self.properties.orbital_coefficients = collect;
+        self
+    }
+
+    /// Keep snapshots of the latest `n` evaluations in `XtbModel`. Snapshots
+    /// include the wavefunction, which could be memory consuming.
+    pub fn keep_history(&mut self, n: usize) -> &mut Self {
+        self.history = n;
+        self
+    }
+
     /// Set xTB class of method
     pub fn method(&mut self, method: impl Into<XtbMethod>) -> &mut Self {
         self.method = method.into();
         self
     }
 
+    /// Load parametrization from parameter file `path` instead of the
+    /// default one, which is useful for modified parameters.
+    pub fn parameter_file(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.parameter_file = path.as_ref().to_owned().into();
+        self
+    }
+
     /// Periodic lattice
     pub fn lattice(&mut self, lattice: impl Into<Option<[f64; 9]>>) -> &mut Self {
         self.lattice = lattice.into();
         self.periodic = [true; 3];
         self
     }
+
+    /// Set solvent for implicit solvation with GBSA model, such as "water",
+    /// "methanol" or "thf". Solvent names are validated by xtb when the
+    /// parametrization is loaded. `None` for gas phase calculation.
+    pub fn solvent<'a>(&mut self, solvent: impl Into<Option<&'a str>>) -> &mut Self {
+        self.solvation.solvent = solvent.into().map(|x| x.to_owned());
+        self
+    }
+
+    /// Set reference state of the solution for implicit solvation.
+    pub fn solution_state(&mut self, state: XtbSolutionState) -> &mut Self {
+        self.solvation.state = state;
+        self
+    }
+
+    /// Set temperature of the solution for implicit solvation in K.
+    pub fn solvent_temperature(&mut self, t: f64) -> &mut Self {
+        assert!(t.is_sign_positive(), "invalid temperature {:?}", t);
+        self.solvation.temperature = t.into();
+        self
+    }
+
+    /// Set number of angular grid points for SASA calculation in implicit
+    /// solvation.
+    pub fn solvent_grid(&mut self, n: usize) -> &mut Self {
+        self.solvation.grid = n.into();
+        self
+    }
 }
 // 392dc74e ends here
 
+// [[file:../xtb.note::e9d0b3a6][e9d0b3a6]]
+/// Calculated results from `XtbModel`. All quantities are in atomic units.
+#[derive(Clone, Debug)]
+pub struct XtbOutput {
+    /// Total energy in Hartree
+    pub energy: f64,
+    /// Gradient in Hartree / Bohr [natoms][3]
+    pub gradient: Vec<f64>,
+    /// Dipole moment in e Bohr
+    pub dipole: [f64; 3],
+    /// Partial charges in e [natoms], not available for GFN-FF
+    pub charges: Option<Vec<f64>>,
+    /// Virial in Hartree [3][3]
+    pub virial: [f64; 9],
+    /// Wiberg bond orders [natoms][natoms], if requested
+    pub bond_orders: Option<Vec<f64>>,
+    /// Number of basis functions, if orbitals requested
+    pub nao: Option<usize>,
+    /// Orbital energies in Hartree [nao], if requested
+    pub orbital_eigenvalues: Option<Vec<f64>>,
+    /// Orbital occupation numbers [nao], if requested
+    pub orbital_occupations: Option<Vec<f64>>,
+    /// Orbital coefficients [nao][nao], if requested
+    pub orbital_coefficients: Option<Vec<f64>>,
+    /// Captured calculation output, if requested
+    pub log: Option<String>,
+}
+// e9d0b3a6 ends here
+
 // [[file:../xtb.note::bcd483ad][bcd483ad]]
 /// High level abstraction for XTB evaluation of energy and gradient
 pub struct XtbModel {
@@ -129,8 +272,57 @@ pub struct XtbModel {
     mol: XtbMolecule,
     calc: XtbCalculator,
 
-    // calculated results
+    // point charges embedding the system
+    external_charges: Option<XtbExternalCharges>,
+
+    // settings applied to the calculator
+    setup: Option<XtbCalculatorSetup>,
+
+    // calculated results, also used for restarting SCC
+    results: Option<XtbResults>,
     dipole: Option<[f64; 3]>,
+    output: Option<String>,
+    evaluated: Option<XtbEvaluation>,
+    history: VecDeque<XtbSnapshot>,
+    // number of parametrization loads
+    n_loads: usize,
+}
+
+/// Structure and results of the last evaluation.
+#[derive(Clone, Debug)]
+struct XtbEvaluation {
+    method: XtbMethod,
+    coord: Vec<f64>,
+    lattice: Option<[f64; 9]>,
+    output: XtbOutput,
+}
+
+/// Calculator settings applied in the last evaluation.
+#[derive(Clone, Debug, PartialEq)]
+struct XtbCalculatorSetup {
+    method: XtbMethod,
+    parameter_file: Option<PathBuf>,
+    solvation: XtbSolvation,
+    lattice: Option<[f64; 9]>,
+    electronic_temperature: f64,
+    max_iterations: usize,
+}
+
+impl XtbCalculatorSetup {
+    /// Return true if the parametrization has to be reloaded to apply `other`.
+    fn requires_reload(&self, other: &Self) -> bool {
+        self.method != other.method
+            || self.parameter_file != other.parameter_file
+            || self.solvation != other.solvation
+            || self.lattice != other.lattice
+    }
+}
+
+/// External point charges for electrostatic embedding.
+struct XtbExternalCharges {
+    numbers: Vec<i32>,
+    charges: Vec<f64>,
+    positions: Vec<f64>,
 }
 
 impl XtbModel {
@@ -144,22 +336,24 @@ impl XtbModel {
         );
         let env = XtbEnvironment::new();
         let params = params.into().unwrap_or_default();
-        match params.verbosity {
-            XtbOutputVerbosity::Verbose => env.set_output_verbose()?,
-            XtbOutputVerbosity::Muted => env.set_output_muted()?,
-            XtbOutputVerbosity::Minimal => env.set_output_minimal()?,
-        }
+        apply_output(&env, &params, None)?;
 
         let uhf = params.uhf as i32;
         let charge = params.charge;
         let lattice = params.lattice;
         let periodic = params.periodic;
         let mol = XtbMolecule::create(&env, &atom_types, coord, charge, uhf, lattice.as_ref(), &periodic)?;
-        let mut calc = XtbCalculator::new();
-        calc.load_parametrization(&mol, &env, params.method)?;
-        let xtb = Self {
+        let calc = XtbCalculator::new();
+        let mut xtb = Self {
             coord: coord.to_vec(),
+            setup: None,
+            results: None,
+            evaluated: None,
+            history: VecDeque::new(),
+            n_loads: 0,
             dipole: None,
+            output: None,
+            external_charges: None,
             lattice,
             periodic,
             mol,
@@ -169,10 +363,133 @@ impl XtbModel {
             atom_types: atom_types.to_vec(),
             env,
         };
+        xtb.capture(|xtb| xtb.setup_calculator())??;
+        // nothing has been evaluated yet
+        xtb.output = None;
 
         Ok(xtb)
     }
 
+    /// Return current calculation parameters.
+    pub fn parameters(&self) -> &XtbParameters {
+        &self.params
+    }
+
+    /// Return atomic numbers of all atoms.
+    pub fn atom_types(&self) -> &[i32] {
+        &self.atom_types
+    }
+
+    /// Return current Cartesian coordinates in Bohr.
+    pub fn coord(&self) -> &[f64] {
+        &self.coord
+    }
+
+    /// Update calculation parameters. Parametrization will be reloaded in
+    /// next evaluation only when it is necessary, for example changes in
+    /// method or solvent.
+    pub fn set_parameters(&mut self, params: XtbParameters) -> Result<()> {
+        if self.external_charges.is_some() {
+            match params.method {
+                XtbMethod::GFN1xTB | XtbMethod::GFN2xTB => {}
+                method => bail!("external point charges are not supported by {:?}", method),
+            }
+        }
+
+        let env = &self.env;
+        apply_output(env, &params, Some(&self.params.output))?;
+
+        // charge, spin and periodicity are immutable in molecular structure data
+        let new_molecule = params.charge != self.params.charge
+            || params.uhf != self.params.uhf
+            || params.periodic != self.params.periodic
+            || params.lattice != self.params.lattice;
+        if new_molecule {
+            if params.lattice != self.params.lattice {
+                self.lattice = params.lattice;
+                self.periodic = params.periodic;
+            }
+            let uhf = params.uhf as i32;
+            let mol = XtbMolecule::create(
+                env,
+                &self.atom_types,
+                &self.coord,
+                params.charge,
+                uhf,
+                self.lattice.as_ref(),
+                &self.periodic,
+            )?;
+            self.mol = mol;
+            // force reloading parametrization for the new molecule
+            self.setup = None;
+            self.results = None;
+        }
+        self.params = params;
+
+        Ok(())
+    }
+
+    /// Load parametrization and apply calculator settings. The
+    /// parametrization is reloaded only if related settings changed since
+    /// last call.
+    fn setup_calculator(&mut self) -> Result<()> {
+        let setup = XtbCalculatorSetup {
+            method: self.params.method,
+            parameter_file: self.params.parameter_file.clone(),
+            solvation: self.params.solvation.clone(),
+            lattice: self.lattice,
+            electronic_temperature: self.params.electronic_temperature,
+            max_iterations: self.params.max_iterations,
+        };
+
+        let env = &self.env;
+        let reload = match &self.setup {
+            Some(old) if old == &setup => return Ok(()),
+            Some(old) => old.requires_reload(&setup),
+            None => true,
+        };
+        if reload {
+            // make sure the setup will be redone if anything fails
+            self.setup = None;
+            // check before loading, which may fail for other reasons
+            if setup.solvation.solvent.is_some() && setup.method == XtbMethod::GFN0xTB {
+                bail!("implicit solvation is not supported by {:?}", setup.method);
+            }
+            let param_file = setup.parameter_file.as_deref();
+            self.calc
+                .load_parametrization_from(&self.mol, env, setup.method, param_file)?;
+            self.n_loads += 1;
+            self.apply_solvent()?;
+            self.apply_external_charges()?;
+            self.calc.set_accuracy(env, 1.0);
+        }
+        self.calc.set_electronic_temperature(env, setup.electronic_temperature);
+        self.calc.set_max_iterations(env, setup.max_iterations);
+        // wavefunction of a different method cannot be used for restart
+        if self.setup.as_ref().map(|x| x.method) != Some(setup.method) {
+            self.results = None;
+        }
+        self.setup = setup.into();
+
+        Ok(())
+    }
+
+    /// Apply implicit solvation model if required. Must be called after
+    /// loading parametrization.
+    fn apply_solvent(&self) -> Result<()> {
+        let solvation = &self.params.solvation;
+        if let Some(solvent) = &solvation.solvent {
+            self.calc.set_solvent(
+                &self.env,
+                solvent,
+                solvation.state,
+                solvation.temperature,
+                solvation.grid,
+            )?;
+        }
+        Ok(())
+    }
+
     /// Update coordinates and lattice parameters (quantities in Bohr).
     pub fn update_structure(&mut self, positions: &[f64], lattice: impl Into<Option<[f64; 9]>>) -> Result<()> {
         assert_eq!(positions.len(), self.coord.len());
@@ -186,32 +503,321 @@ impl XtbModel {
         Ok(())
     }
 
-    /// Call XTB for evaluation of energy and gradient. coord in bohr.
-    pub fn calculate_energy_and_gradient(&mut self, gradient: &mut [f64]) -> Result<f64> {
+    /// Call XTB for evaluation of energy, gradient and other available
+    /// properties. Expensive properties such as bond orders or orbitals are
+    /// collected only when requested in `XtbParameters`.
+    pub fn calculate(&mut self) -> Result<XtbOutput> {
+        self.mol.update(&self.env, &self.coord, self.lattice.as_ref())?;
+        self.capture(|xtb| {
+            xtb.setup_calculator()?;
+            // reuse previous wavefunction as initial guess
+            let res = xtb.results.get_or_insert_with(XtbResults::new);
+            let status = xtb.calc.single_point_into(&xtb.mol, &xtb.env, res);
+            if status.is_err() {
+                // the wavefunction could be unusable for restart
+                xtb.results = None;
+            }
+            status
+        })??;
+
         let env = &self.env;
-        let mol = &self.mol;
+        let res = self.results.as_ref().expect("xtb results");
 
-        mol.update(env, &self.coord, self.lattice.as_ref())?;
-        self.calc.load_parametrization(mol, env, self.params.method)?;
-        self.calc.set_accuracy(env, 1.0);
-        self.calc
-            .set_electronic_temperature(env, self.params.electronic_temperature);
-        self.calc.set_max_iterations(env, self.params.max_iterations);
-        let res = self.calc.single_point(mol, env)?;
         let energy = res.get_energy(env)?;
-        res.get_gradient(env, gradient)?;
-        self.dipole = res.get_dipole(env)?.into();
+        let gradient = res.gradient(env)?;
+        let dipole = res.get_dipole(env)?;
+        // partial charges are not available from GFN-FF
+        let charges = match self.params.method {
+            XtbMethod::GFNFF => None,
+            _ => Some(res.charges(env)?),
+        };
+        let virial: [f64; 9] = res
+            .virial(env)?
+            .try_into()
+            .map_err(|x: Vec<f64>| format_err!("invalid size of virial: {}", x.len()))?;
+
+        let props = &self.params.properties;
+        let bond_orders = if props.bond_orders {
+            Some(res.bond_orders(env)?)
+        } else {
+            None
+        };
+        let nao = if props.orbital_energies || props.orbital_coefficients {
+            Some(res.get_nao(env)?)
+        } else {
+            None
+        };
+        let (orbital_eigenvalues, orbital_occupations) = if props.orbital_energies {
+            (Some(res.orbital_eigenvalues(env)?), Some(res.orbital_occupations(env)?))
+        } else {
+            (None, None)
+        };
+        let orbital_coefficients = if props.orbital_coefficients {
+            Some(res.orbital_coefficients(env)?)
+        } else {
+            None
+        };
 
-        Ok(energy)
+        self.dipole = dipole.into();
+
+        let output = XtbOutput {
+            energy,
+            gradient,
+            dipole,
+            charges,
+            virial,
+            bond_orders,
+            nao,
+            orbital_eigenvalues,
+            orbital_occupations,
+            orbital_coefficients,
+            log: self.output.clone(),
+        };
+        self.evaluated = Some(XtbEvaluation {
+            method: self.params.method,
+            coord: self.coord.clone(),
+            lattice: self.lattice,
+            output: output.clone(),
+        });
+        if self.params.history > 0 {
+            if let Some(snapshot) = self.snapshot() {
+                while self.history.len() >= self.params.history {
+                    self.history.pop_front();
+                }
+                self.history.push_back(snapshot);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Call XTB for evaluation of energy and gradient. coord in bohr.
+    pub fn calculate_energy_and_gradient(&mut self, gradient: &mut [f64]) -> Result<f64> {
+        let output = self.calculate()?;
+        gradient.clone_from_slice(&output.gradient);
+
+        Ok(output.energy)
     }
 
     /// Return last evaluated dipole moment. Return None if not calculated yet.
     pub fn get_dipole(&self) -> Option<[f64; 3]> {
         self.dipole
     }
+
+    /// Discard the wavefunction from previous evaluation, so that the next
+    /// evaluation will start SCC from scratch.
+    pub fn reset_guess(&mut self) {
+        self.results = None;
+    }
+
+    /// Return how many times the parametrization has been loaded since
+    /// creation.
+    pub fn parametrization_loads(&self) -> usize {
+        self.n_loads
+    }
+
+    /// Return captured calculation output of last evaluation. Return None if
+    /// output capturing is not enabled in `XtbParameters`.
+    pub fn get_output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+
+    /// Call `f` with calculation output captured if requested, including
+    /// output from loading parametrization.
+    fn capture<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Result<T> {
+        if self.params.output != XtbOutputTarget::Capture {
+            return Ok(f(self));
+        }
+        let path = capture_file_path();
+        self.env.set_output_file(&path)?;
+        let ret = f(self);
+        self.env.release_output()?;
+        self.output = std::fs::read_to_string(&path).ok();
+        let _ = std::fs::remove_file(&path);
+        Ok(ret)
+    }
+}
+
+impl Drop for XtbModel {
+    fn drop(&mut self) {
+        if let XtbOutputTarget::File(_) = &self.params.output {
+            let _ = self.env.release_output();
+        }
+    }
+}
+
+/// Apply output verbosity and target in `params` to `env`. Output file of
+/// `old` target is released if the target changes.
+fn apply_output(env: &XtbEnvironment, params: &XtbParameters, old: Option<&XtbOutputTarget>) -> Result<()> {
+    match params.verbosity {
+        XtbOutputVerbosity::Verbose => env.set_output_verbose()?,
+        XtbOutputVerbosity::Muted => env.set_output_muted()?,
+        XtbOutputVerbosity::Minimal => env.set_output_minimal()?,
+    }
+    if old != Some(&params.output) {
+        if let Some(XtbOutputTarget::File(_)) = old {
+            env.release_output()?;
+        }
+        if let XtbOutputTarget::File(path) = &params.output {
+            env.set_output_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Return a unique temporary file path for capturing calculation output.
+fn capture_file_path() -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("xtb-model-{}-{}.log", std::process::id(), n))
 }
 // bcd483ad ends here
 
+// [[file:../xtb.note::8f4a61d3][8f4a61d3]]
+/// Snapshot of an evaluation in `XtbModel`, including the structure,
+/// calculated results and the wavefunction.
+#[derive(Clone)]
+pub struct XtbSnapshot {
+    evaluated: XtbEvaluation,
+    results: XtbResults,
+}
+
+impl XtbSnapshot {
+    /// Return evaluated positions in Bohr.
+    pub fn coord(&self) -> &[f64] {
+        &self.evaluated.coord
+    }
+
+    /// Return lattice parameters in Bohr if any.
+    pub fn lattice(&self) -> Option<[f64; 9]> {
+        self.evaluated.lattice
+    }
+
+    /// Return calculated results.
+    pub fn output(&self) -> &XtbOutput {
+        &self.evaluated.output
+    }
+
+    /// Return calculated total energy in Hartree.
+    pub fn energy(&self) -> f64 {
+        self.evaluated.output.energy
+    }
+}
+
+impl XtbModel {
+    /// Take a snapshot of the last evaluation, which could be used to roll
+    /// back later. Return None if not calculated yet.
+    pub fn snapshot(&self) -> Option<XtbSnapshot> {
+        let snapshot = XtbSnapshot {
+            evaluated: self.evaluated.clone()?,
+            results: self.results.clone()?,
+        };
+        Some(snapshot)
+    }
+
+    /// Roll back to the structure and results in `snapshot`. The wavefunction
+    /// in `snapshot` will be used as initial guess for next evaluation.
+    pub fn restore(&mut self, snapshot: &XtbSnapshot) -> Result<()> {
+        let evaluated = &snapshot.evaluated;
+        ensure!(evaluated.coord.len() == self.coord.len(), "snapshot of a different system");
+        ensure!(
+            evaluated.method == self.params.method,
+            "snapshot evaluated with a different method: {:?}",
+            evaluated.method
+        );
+        // periodicity is fixed in molecular structure data
+        ensure!(
+            evaluated.lattice.is_some() == self.lattice.is_some(),
+            "snapshot evaluated with a different periodicity"
+        );
+        self.coord.clone_from(&evaluated.coord);
+        self.lattice = evaluated.lattice;
+        self.results = snapshot.results.clone().into();
+        self.dipole = evaluated.output.dipole.into();
+        self.evaluated = evaluated.clone().into();
+
+        Ok(())
+    }
+
+    /// Return snapshots of the latest evaluations, from the oldest to the
+    /// newest. The number of snapshots kept is set by
+    /// `XtbParameters::keep_history`.
+    pub fn history(&self) -> impl Iterator<Item = &XtbSnapshot> {
+        self.history.iter()
+    }
+
+    /// Remove all snapshots in history.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+}
+// 8f4a61d3 ends here
+
+// [[file:../xtb.note::f1c86b52][f1c86b52]]
+impl XtbModel {
+    /// Embed the system in external point charges for QM/MM calculation
+    /// (only supported in GFN1-xTB and GFN2-xTB). Previous point charges
+    /// will be replaced. Gradient acting on the point charges is not
+    /// available from the C API of xtb.
+    ///
+    /// # Parameters
+    ///
+    /// * numbers: atomic numbers of point charges, used for chemical hardness
+    /// * charges: point charges in e
+    /// * positions: positions of point charges in Bohr
+    pub fn set_external_charges(&mut self, numbers: &[i32], charges: &[f64], positions: &[f64]) -> Result<()> {
+        match self.params.method {
+            XtbMethod::GFN1xTB | XtbMethod::GFN2xTB => {}
+            method => bail!("external point charges are not supported by {:?}", method),
+        }
+        // keep the previous point charges if the new ones are rejected
+        self.calc.set_external_charges(&self.env, numbers, charges, positions)?;
+        let pc = XtbExternalCharges {
+            numbers: numbers.to_vec(),
+            charges: charges.to_vec(),
+            positions: positions.to_vec(),
+        };
+        self.external_charges = pc.into();
+
+        Ok(())
+    }
+
+    /// Update positions of external point charges (in Bohr) between steps.
+    pub fn update_external_charges(&mut self, positions: &[f64]) -> Result<()> {
+        let pc = self
+            .external_charges
+            .as_mut()
+            .ok_or_else(|| format_err!("no external point charges to update"))?;
+        ensure!(positions.len() == pc.positions.len(), "invalid size of point charge positions");
+        self.calc
+            .set_external_charges(&self.env, &pc.numbers, &pc.charges, positions)?;
+        pc.positions.clone_from_slice(positions);
+
+        Ok(())
+    }
+
+    /// Remove external point charges.
+    pub fn release_external_charges(&mut self) -> Result<()> {
+        if self.external_charges.take().is_some() {
+            self.calc.release_external_charges(&self.env)?;
+        }
+        Ok(())
+    }
+
+    /// Apply external point charges if any. Must be called after loading
+    /// parametrization.
+    fn apply_external_charges(&self) -> Result<()> {
+        if let Some(pc) = &self.external_charges {
+            self.calc
+                .set_external_charges(&self.env, &pc.numbers, &pc.charges, &pc.positions)?;
+        }
+        Ok(())
+    }
+}
+// f1c86b52 ends here
+
 // [[file:../xtb.note::2398beeb][2398beeb]]
 #[test]
 fn test_xtb_method_into() {
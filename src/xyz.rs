@@ -0,0 +1,39 @@
+// [[file:../xtb.note::6b2e8d41][6b2e8d41]]
+//! Output of structures in xyz format
+// 6b2e8d41 ends here
+
+// [[file:../xtb.note::d17a4c95][d17a4c95]]
+use crate::elements::{element_symbol, ANGSTROM_TO_BOHR};
+
+use anyhow::*;
+use std::io::Write;
+use std::path::Path;
+// d17a4c95 ends here
+
+// [[file:../xtb.note::e8c03f5a][e8c03f5a]]
+/// Write `frames` of comment line and positions in Bohr into `path` in xyz
+/// format with coordinates in Angstrom.
+pub(crate) fn write_xyz<'a, I>(path: impl AsRef<Path>, atom_types: &[i32], frames: I) -> Result<()>
+where
+    I: IntoIterator<Item = (String, &'a [f64])>,
+{
+    let symbols = atom_types
+        .iter()
+        .map(|&z| element_symbol(z).ok_or_else(|| format_err!("no element symbol for {z}")))
+        .collect::<Result<Vec<_>>>()?;
+    let path = path.as_ref();
+    let f = std::fs::File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut f = std::io::BufWriter::new(f);
+    for (comment, positions) in frames {
+        ensure!(positions.len() == 3 * symbols.len(), "positions do not match {} atoms", symbols.len());
+        writeln!(f, "{}", symbols.len())?;
+        writeln!(f, "{}", comment)?;
+        for (sym, r) in symbols.iter().zip(positions.chunks_exact(3)) {
+            let [x, y, z] = [0, 1, 2].map(|k| r[k] / ANGSTROM_TO_BOHR);
+            writeln!(f, "{:2} {:16.10} {:16.10} {:16.10}", sym, x, y, z)?;
+        }
+    }
+    f.flush()?;
+    Ok(())
+}
+// e8c03f5a ends here
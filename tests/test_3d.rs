@@ -40,7 +40,7 @@ fn test_xtb_3d() -> Result<()> {
 
     let periodic = [true; 3];
     let env = XtbEnvironment::new();
-    env.set_output_verbose();
+    env.set_output_verbose()?;
     let mol = XtbMolecule::create(&env, &numbers, &coord, 0.0, 0, &lattice, &periodic)?;
     let calc = XtbCalculator::new();
     calc.load_parametrization(&mol, &env, XtbMethod::GFN2xTB)?;
@@ -48,19 +48,23 @@ fn test_xtb_3d() -> Result<()> {
     // It should fail for periodic input: Multipoles not available with PBC
     let res = calc.single_point(&mol, &env);
     assert!(res.is_err());
+    let err = res.err().unwrap();
+    let err = err.downcast_ref::<XtbError>().unwrap();
+    assert_eq!(err.kind(), XtbErrorKind::UnsupportedPeriodic);
+    assert_eq!(err.call(), Some("xtb_singlepoint"));
 
     calc.load_parametrization(&mol, &env, XtbMethod::GFN1xTB)?;
     let res = calc.single_point(&mol, &env)?;
     let energy = res.get_energy(&env)?;
     assert_relative_eq!(energy, -31.906084801853034, epsilon=1e-9);
-    let mut gradient = coord.clone();
+    let mut gradient = coord;
     res.get_gradient(&env, &mut gradient)?;
     assert_relative_eq!(gradient[0], 5.46952312e-03, epsilon=1e-9);
 
     let mut params = XtbParameters::default();
     params.output_muted().method("GFN1-xTB").lattice(lattice);
     let mut xtb = XtbModel::create(&numbers, &coord, params)?;
-    let mut gradient = coord.clone();
+    let mut gradient = coord;
     let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
     assert_relative_eq!(energy, -31.906084801853034, epsilon=1e-9);
     assert_relative_eq!(gradient[0], 5.46952312e-03, epsilon=1e-9);
@@ -68,12 +72,28 @@ fn test_xtb_3d() -> Result<()> {
     assert_relative_eq!(gradient[58], -0.000601295157, epsilon=1e-9);
 
     // test update
-    let mut coord = coord.clone();
+    let mut coord = coord;
     coord[0] = 0.477104501;
     xtb.update_structure(&coord, None)?;
     let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
     assert_relative_eq!(energy, -31.872139660919, epsilon=1e-9);
 
+    // restoring a snapshot also restores its lattice
+    let snapshot = xtb.snapshot().unwrap();
+    let scaled = lattice.map(|x| x * 1.01);
+    xtb.update_structure(&coord, scaled)?;
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    xtb.restore(&snapshot)?;
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, -31.872139660919, epsilon=1e-9);
+
+    // but not one without lattice
+    let mut params = XtbParameters::default();
+    params.output_muted().method("GFN1-xTB");
+    let mut xtb_mol = XtbModel::create(&numbers, &coord, params)?;
+    xtb_mol.calculate_energy_and_gradient(&mut gradient)?;
+    assert!(xtb.restore(&xtb_mol.snapshot().unwrap()).is_err());
+
     Ok(())
 }
 // 0eb1a5c9 ends here
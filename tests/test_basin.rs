@@ -0,0 +1,43 @@
+// [[file:../xtb.note::5d8e3b16][5d8e3b16]]
+use anyhow::*;
+use xtb_model::basin::*;
+use xtb_model::internal::fragments;
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_basin_hopping_water_dimer() -> Result<()> {
+    let atom_types = [8, 1, 1, 8, 1, 1];
+    let coord = [
+        0.0, 0.0, 0.0, 1.81, 0.0, 0.0, -0.45, 1.75, 0.0, // water 1
+        5.5, 0.0, 0.0, 6.1, 1.5, 0.8, 6.1, -1.5, 0.8, // water 2
+    ];
+    assert_eq!(fragments(&atom_types, &coord)?, [[0, 1, 2], [3, 4, 5]]);
+
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&atom_types, &coord, params)?;
+    let mut n = 0;
+    let report = BasinHopping::new(&atom_types)
+        .steps(5)
+        .max_minima(3)
+        .seed(1)
+        .run_with(&mut xtb, &coord, |p| {
+            assert_eq!(p.step, n);
+            n += 1;
+            true
+        })?;
+    assert_eq!(report.n_steps, 5);
+    assert!(!report.minima.is_empty() && report.minima.len() <= 3);
+    // permutations of the same minimum are not archived again
+    for w in report.minima.windows(2) {
+        assert!(w[1].energy - w[0].energy >= 1e-5);
+    }
+
+    let path = std::env::temp_dir().join("test_basin_hopping.xyz");
+    report.write_xyz(&path)?;
+    let n_lines = std::fs::read_to_string(&path)?.lines().count();
+    assert_eq!(n_lines, 8 * report.minima.len());
+
+    Ok(())
+}
+// 5d8e3b16 ends here
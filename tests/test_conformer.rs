@@ -0,0 +1,56 @@
+// [[file:../xtb.note::3e6b0c97][3e6b0c97]]
+use anyhow::*;
+use xtb_model::conformer::*;
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_conformer_ethanol() -> Result<()> {
+    let atom_types = [6, 6, 8, 1, 1, 1, 1, 1, 1];
+    #[rustfmt::skip]
+    let coord: Vec<_> = [
+        0.000,  0.000,  0.000, // C
+        1.520,  0.000,  0.000, // C
+        2.010,  1.340,  0.000, // O
+        2.980,  1.300,  0.000, // H on O
+        -0.380,  1.020,  0.000,
+        -0.380, -0.510,  0.880,
+        -0.380, -0.510, -0.880,
+        1.900, -0.510,  0.880,
+        1.900, -0.510, -0.880,
+    ]
+    .iter()
+    .map(|x| x / 0.52917721092)
+    .collect();
+
+    // the only rotatable bond is C-O, the C-C bond is excluded since it
+    // rotates only the methyl group
+    assert_eq!(rotatable_bonds(&atom_types, &coord)?, [(1, 2)]);
+
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&atom_types, &coord, params.clone())?;
+    let ensemble = ConformerSearch::new(&atom_types).run(&mut xtb, &coord)?;
+    assert_eq!(ensemble.n_samples, 3);
+    assert_eq!(ensemble.n_failed, 0);
+    assert!(!ensemble.conformers.is_empty());
+    assert_eq!(ensemble.lowest().relative_energy, 0.0);
+    for w in ensemble.conformers.windows(2) {
+        assert!(w[0].energy <= w[1].energy);
+    }
+    let total: f64 = ensemble.conformers.iter().map(|c| c.weight).sum();
+    assert!((total - 1.0).abs() < 1e-10);
+
+    let path = std::env::temp_dir().join("test_conformer_ethanol.xyz");
+    ensemble.write_xyz(&path)?;
+    let n_lines = std::fs::read_to_string(&path)?.lines().count();
+    assert_eq!(n_lines, 11 * ensemble.conformers.len());
+
+    // GFN-FF preoptimization and GFN2-xTB refinement
+    params.method("GFN-FF");
+    let mut gfnff = XtbModel::create(&atom_types, &coord, params)?;
+    let refined = ConformerSearch::new(&atom_types).run_refined(&mut gfnff, &mut xtb, &coord)?;
+    assert!((refined.lowest().energy - ensemble.lowest().energy).abs() < 1e-3);
+
+    Ok(())
+}
+// 3e6b0c97 ends here
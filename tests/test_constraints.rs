@@ -0,0 +1,41 @@
+// [[file:../xtb.note::e47c2a09][e47c2a09]]
+use anyhow::*;
+use xtb_model::constraints::*;
+use xtb_model::internal::InternalCoordinate;
+use xtb_model::opt::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::XtbModel;
+
+#[test]
+fn test_constrained_opt() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let xtb = XtbModel::create(&ATOM_TYPES, &coord, None)?;
+
+    let mut constraints = Constraints::default();
+    constraints
+        .force_constant(2.0)
+        .freeze_atoms(&[0])
+        .distance(1, 2, 2.4)
+        .angle(3, 0, 4, None);
+    let mut pot = ConstrainedPotential::new(xtb, constraints);
+    let report = Lbfgs::default().max_steps(200).minimize(&mut pot, &coord)?;
+    assert!(report.converged());
+
+    // frozen atom stays in place
+    assert_eq!(report.positions[..3], coord[..3]);
+    let r = InternalCoordinate::Bond(1, 2).value(&report.positions);
+    assert!((r - 2.4).abs() < 0.05, "{r}");
+    let a0 = InternalCoordinate::Angle(3, 0, 4).value(&coord);
+    let a = InternalCoordinate::Angle(3, 0, 4).value(&report.positions);
+    assert!((a - a0).abs().to_degrees() < 2.0);
+    assert!(pot.restraint_energy() > 0.0);
+
+    // invalid atom index
+    let mut constraints = Constraints::default();
+    constraints.dihedral(0, 1, 2, 7, 180.0);
+    let mut pot = ConstrainedPotential::new(pot.into_inner(), constraints);
+    assert!(Lbfgs::default().minimize(&mut pot, &coord).is_err());
+
+    Ok(())
+}
+// e47c2a09 ends here
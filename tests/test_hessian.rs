@@ -0,0 +1,51 @@
+// [[file:../xtb.note::85f0c3e2][85f0c3e2]]
+use anyhow::*;
+use xtb_model::hessian::*;
+use xtb_model::opt::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_hessian_frequencies() -> Result<()> {
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, params.clone())?;
+    let report = Lbfgs::default()
+        .convergence(OptConvergence::tight())
+        .minimize(&mut xtb, &ATOM_COORDS)?;
+    let coord = report.positions;
+
+    let hessian = NumericalHessian::default().compute(&mut xtb, &coord)?;
+    let hessian_par = NumericalHessian::default()
+        .threads(2)
+        .compute_parallel(|| XtbModel::create(&ATOM_TYPES, &coord, params.clone()), &coord)?;
+    assert_eq!(hessian.len(), 21 * 21);
+    for (a, b) in hessian.iter().zip(&hessian_par) {
+        assert!((a - b).abs() < 1e-6);
+    }
+
+    let vib = VibrationalAnalysis::new(&ATOM_TYPES)?;
+    let modes = vib.normal_modes(&coord, &hessian)?;
+    assert!(!modes.linear);
+    assert_eq!(modes.frequencies.len(), 15);
+    assert_eq!(modes.modes.len(), 15);
+    assert_eq!(modes.n_imaginary(), 0);
+    // small imaginary frequencies are regarded as numerical noise
+    let mut noisy = modes.clone();
+    noisy.frequencies[0] = -15.0;
+    assert_eq!(noisy.n_imaginary(), 0);
+    noisy.imaginary_cutoff = 10.0;
+    assert_eq!(noisy.n_imaginary(), 1);
+    // C-H stretching
+    let fmax = modes.frequencies[14];
+    assert!(fmax > 2900.0 && fmax < 3600.0, "{fmax}");
+
+    // deuteration lowers the highest frequency
+    let mut vib = VibrationalAnalysis::new(&ATOM_TYPES)?;
+    vib.mass(6, 2.014);
+    let modes_d = vib.normal_modes(&coord, &hessian)?;
+    assert!(modes_d.frequencies[14] < fmax);
+
+    Ok(())
+}
+// 85f0c3e2 ends here
@@ -0,0 +1,50 @@
+// [[file:../xtb.note::d4a90b6e][d4a90b6e]]
+use anyhow::*;
+use xtb_model::hessian::*;
+use xtb_model::ir::*;
+use xtb_model::opt::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::XtbModel;
+
+#[test]
+fn test_ir_spectrum() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let report = Lbfgs::default()
+        .convergence(OptConvergence::tight())
+        .minimize(&mut xtb, &ATOM_COORDS)?;
+    let coord = report.positions;
+
+    let numerical = NumericalHessian::default();
+    let hd = numerical.compute_with_dipole(&mut xtb, &coord)?;
+    assert_eq!(hd.dipole_derivatives.len(), 21 * 3);
+    let hessian = numerical.compute(&mut xtb, &coord)?;
+    for (a, b) in hessian.iter().zip(&hd.hessian) {
+        assert!((a - b).abs() < 1e-6);
+    }
+    let hd_par =
+        numerical.compute_with_dipole_parallel(|| XtbModel::create(&ATOM_TYPES, &coord, None), &coord)?;
+    for (a, b) in hd.dipole_derivatives.iter().zip(&hd_par.dipole_derivatives) {
+        assert!((a - b).abs() < 1e-6);
+    }
+
+    let modes = VibrationalAnalysis::new(&ATOM_TYPES)?.normal_modes(&coord, &hd.hessian)?;
+    let ir = IrSpectrum::new(&modes, &hd.dipole_derivatives)?;
+    assert_eq!(ir.intensities.len(), 15);
+    assert!(ir.intensities.iter().all(|&x| x >= 0.0));
+    assert!(ir.intensities.iter().any(|&x| x > 1.0));
+
+    let mut broadening = Broadening::default();
+    broadening.shape(LineShape::Gaussian).width(20.0).range(500.0, 3500.0).step(2.0);
+    let spectrum = ir.broaden(&broadening);
+    assert_eq!(spectrum.len(), 1501);
+    assert_eq!(spectrum[0][0], 500.0);
+
+    let path = std::env::temp_dir().join("xtb-model-test-ir.dat");
+    ir.write_broadened(&path, &broadening)?;
+    let s = std::fs::read_to_string(&path)?;
+    assert_eq!(s.lines().filter(|l| !l.starts_with('#')).count(), 1501);
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+// d4a90b6e ends here
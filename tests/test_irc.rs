@@ -0,0 +1,46 @@
+// [[file:../xtb.note::a31f6c84][a31f6c84]]
+use anyhow::*;
+use xtb_model::hessian::NumericalHessian;
+use xtb_model::irc::*;
+use xtb_model::opt::OptConvergence;
+use xtb_model::ts::Prfo;
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_irc_hcn() -> Result<()> {
+    let atom_types = [6, 7, 1];
+    let coord = [0.0, 0.0, 0.0, 0.0, 0.0, 2.25, 2.0, 0.0, 1.0];
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&atom_types, &coord, params)?;
+
+    let ts = Prfo::new(&atom_types)?
+        .convergence(OptConvergence::tight())
+        .search(&mut xtb, &coord)?;
+    assert!(ts.is_transition_state());
+    let hessian = NumericalHessian::default().compute(&mut xtb, &ts.positions)?;
+
+    let report = Irc::new(&atom_types)?.step_size(0.2).run(&mut xtb, &ts.positions, &hessian)?;
+    assert!(report.forward_converged);
+    assert!(report.backward_converged);
+    assert!(report.ts().s == 0.0);
+    for w in report.points[..=report.ts_index].windows(2) {
+        assert!(w[1].energy >= w[0].energy - 1e-8);
+    }
+    for w in report.points[report.ts_index..].windows(2) {
+        assert!(w[1].energy <= w[0].energy + 1e-8);
+    }
+
+    // one end is HCN and the other HNC
+    let dist = |x: &[f64], i: usize, j: usize| (0..3).map(|k| (x[3 * i + k] - x[3 * j + k]).powi(2)).sum::<f64>().sqrt();
+    let hcn = |p: &IrcPoint| dist(&p.positions, 2, 0) < dist(&p.positions, 2, 1);
+    assert_ne!(hcn(report.forward_end()), hcn(report.backward_end()));
+
+    let path = std::env::temp_dir().join("test_irc_hcn.xyz");
+    report.write_xyz(&path)?;
+    let n_lines = std::fs::read_to_string(&path)?.lines().count();
+    assert_eq!(n_lines, 5 * report.points.len());
+
+    Ok(())
+}
+// a31f6c84 ends here
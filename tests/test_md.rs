@@ -0,0 +1,90 @@
+// [[file:../xtb.note::2e7a5c91][2e7a5c91]]
+use anyhow::*;
+use xtb_model::md::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::XtbModel;
+
+#[test]
+fn test_md_nve() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let mut md = Dynamics::new(&ATOM_TYPES)?;
+    md.time_step(0.5).initial_temperature(300.0).seed(1);
+
+    let mut energies = vec![];
+    let report = md.run_with(&mut xtb, &ATOM_COORDS, 20, |s| {
+        if s.step == 0 {
+            // initial velocities are scaled to the requested temperature
+            assert!((s.temperature - 300.0).abs() < 1e-6);
+        }
+        energies.push(s.total_energy);
+        true
+    })?;
+    assert_eq!(report.n_steps, 20);
+    assert_eq!(energies.len(), 21);
+    let drift = energies.iter().map(|e| (e - energies[0]).abs()).fold(0.0, f64::max);
+    assert!(drift < 1e-4, "{drift}");
+
+    // no center of mass motion
+    let masses = md.masses();
+    for x in 0..3 {
+        let p: f64 = (0..7).map(|i| masses[i] * report.velocities[3 * i + x]).sum();
+        assert!(p.abs() < 1e-8);
+    }
+
+    // stop by callback
+    let report = md.run_with(&mut xtb, &ATOM_COORDS, 20, |s| s.step < 5)?;
+    assert_eq!(report.n_steps, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_md_thermostats() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let path = std::env::temp_dir().join("xtb-model-test-md.log");
+    for thermostat in [
+        Thermostat::Berendsen { temperature: 500.0, tau: 10.0 },
+        Thermostat::Bussi { temperature: 500.0, tau: 10.0 },
+        Thermostat::Langevin { temperature: 500.0, friction: 0.1 },
+    ] {
+        let mut md = Dynamics::new(&ATOM_TYPES)?;
+        md.time_step(1.0).thermostat(thermostat).seed(2).log_file(&path, 5);
+        let report = md.run(&mut xtb, &ATOM_COORDS, 20)?;
+        assert!(report.average_temperature > 100.0);
+        let log = std::fs::read_to_string(&path)?;
+        assert_eq!(log.lines().filter(|l| !l.starts_with('#')).count(), 5);
+    }
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_md_shake() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let bond = |x: &[f64], i: usize, j: usize| -> f64 { (0..3).map(|k| (x[3 * i + k] - x[3 * j + k]).powi(2)).sum::<f64>().sqrt() };
+
+    let mut md = Dynamics::new(&ATOM_TYPES)?;
+    md.time_step(2.0).seed(3).shake(Shake::Hydrogen);
+    let report = md.run(&mut xtb, &ATOM_COORDS, 20)?;
+    // C-H bonds fixed, C-C bonds free
+    assert!((bond(&report.positions, 0, 3) - bond(&ATOM_COORDS, 0, 3)).abs() < 1e-6);
+    assert!((bond(&report.positions, 2, 6) - bond(&ATOM_COORDS, 2, 6)).abs() < 1e-6);
+    assert!((bond(&report.positions, 0, 1) - bond(&ATOM_COORDS, 0, 1)).abs() > 1e-6);
+
+    // hydrogen mass repartitioning conserves total mass
+    md.time_step(4.0).shake(Shake::All).hydrogen_mass(4.0);
+    let masses = md.effective_masses(&ATOM_COORDS)?;
+    assert_eq!(masses[3], 4.0);
+    let mtot: f64 = md.masses().iter().sum();
+    assert!((masses.iter().sum::<f64>() - mtot).abs() < 1e-10);
+    let report = md.run(&mut xtb, &ATOM_COORDS, 10)?;
+    assert!((bond(&report.positions, 0, 1) - bond(&ATOM_COORDS, 0, 1)).abs() < 1e-6);
+
+    // explicit bonds
+    md.shake(Shake::Bonds(vec![(0, 7)]));
+    assert!(md.run(&mut xtb, &ATOM_COORDS, 10).is_err());
+
+    Ok(())
+}
+// 2e7a5c91 ends here
@@ -0,0 +1,45 @@
+// [[file:../xtb.note::b6f02e48][b6f02e48]]
+use anyhow::*;
+use approx::assert_relative_eq;
+use xtb_model::md::Dynamics;
+use xtb_model::metadyn::Metadynamics;
+use xtb_model::rmsd::rmsd;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::{Potential, XtbModel};
+
+#[test]
+fn test_metadyn() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let mut g0 = vec![0.0; ATOM_COORDS.len()];
+    let e0 = xtb.evaluate(&ATOM_COORDS, &mut g0)?;
+
+    // a reference at the same structure adds a constant bias
+    let mut mtd = Metadynamics::new(&mut xtb);
+    mtd.push_strength(0.01).width(1.0).interval(0);
+    mtd.add_reference(&ATOM_COORDS);
+    let mut g = vec![0.0; ATOM_COORDS.len()];
+    let e = mtd.evaluate(&ATOM_COORDS, &mut g)?;
+    assert_relative_eq!(e - e0, 0.01, epsilon = 1e-10);
+    assert_relative_eq!(mtd.bias_energy(), 0.01, epsilon = 1e-10);
+    assert_relative_eq!(g.as_slice(), g0.as_slice(), epsilon = 1e-8);
+
+    // rigid motion does not change the bias
+    let shifted: Vec<_> = ATOM_COORDS.chunks(3).flat_map(|r| [-r[1] + 0.5, r[0], r[2] - 0.3]).collect();
+    assert!(rmsd(&ATOM_COORDS, &shifted) < 1e-10);
+    mtd.evaluate(&shifted, &mut g)?;
+    assert_relative_eq!(mtd.bias_energy(), 0.01, epsilon = 1e-10);
+
+    // deposition of references during dynamics
+    mtd.clear();
+    mtd.interval(5).max_references(3);
+    let mut md = Dynamics::new(&ATOM_TYPES)?;
+    md.time_step(1.0).initial_temperature(300.0).seed(5);
+    md.run(&mut mtd, &ATOM_COORDS, 10)?;
+    assert_eq!(mtd.references().len(), 2);
+    md.run(&mut mtd, &ATOM_COORDS, 10)?;
+    assert_eq!(mtd.references().len(), 3);
+    assert!(mtd.bias_energy() > 0.0);
+
+    Ok(())
+}
+// b6f02e48 ends here
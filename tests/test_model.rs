@@ -2,6 +2,7 @@
 use anyhow::*;
 use approx::assert_relative_eq;
 use xtb_model::test::ATOM_COORDS;
+use xtb_model::libxtb::XtbError;
 use xtb_model::{XtbModel, XtbParameters};
 
 #[test]
@@ -10,7 +11,7 @@ fn test_xtb_model() -> Result<()> {
     let attyp = [6, 6, 6, 1, 1, 1, 1];
 
     let mut xtb = XtbModel::create(&attyp, &coord, None)?;
-    let mut gradient = coord.clone();
+    let mut gradient = coord;
     let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
     let dipole = xtb.get_dipole().unwrap();
 
@@ -30,3 +31,308 @@ fn test_xtb_model() -> Result<()> {
     Ok(())
 }
 // 6da62560 ends here
+
+// [[file:../xtb.note::8e2b6d14][8e2b6d14]]
+#[test]
+fn test_xtb_model_solvation() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+
+    let mut gradient = coord;
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    let energy_gas = xtb.calculate_energy_and_gradient(&mut gradient)?;
+
+    let mut params = XtbParameters::default();
+    params.solvent("water");
+    let mut xtb = XtbModel::create(&attyp, &coord, params.clone())?;
+    let energy_water = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert!(energy_water < energy_gas);
+    // the solvation model should be kept for subsequent evaluations
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy_water, epsilon = 1e-9);
+
+    // solvents known to xtb beyond the common ones
+    params.solvent("octanol");
+    XtbModel::create(&attyp, &coord, params.clone())?.calculate_energy_and_gradient(&mut gradient)?;
+
+    // invalid solvent name is reported by xtb
+    params.solvent("unknown-solvent");
+    let err = XtbModel::create(&attyp, &coord, params.clone()).err().unwrap();
+    let err = err.downcast_ref::<XtbError>().unwrap();
+    assert_eq!(err.call(), Some("xtb_setSolvent"));
+
+    // GFN0-xTB has no solvation model
+    params.solvent("water").method("GFN0-xTB");
+    let err = XtbModel::create(&attyp, &coord, params).err().unwrap();
+    assert!(err.to_string().contains("implicit solvation is not supported"), "{err}");
+
+    Ok(())
+}
+// 8e2b6d14 ends here
+
+// [[file:../xtb.note::c47a0e93][c47a0e93]]
+#[test]
+fn test_xtb_model_external_charges() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+
+    let mut gradient = coord;
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    let energy_gas = xtb.calculate_energy_and_gradient(&mut gradient)?;
+
+    // a water-like point charge pair next to the molecule
+    let numbers = [8, 1];
+    let charges = [-0.8, 0.4];
+    let positions = [0.0, 0.0, 10.0, 0.0, 1.5, 10.8];
+    xtb.set_external_charges(&numbers, &charges, &positions)?;
+    let energy_pc = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert!((energy_pc - energy_gas).abs() > 1e-6);
+
+    // move the point charges far away
+    let positions = [0.0, 0.0, 1000.0, 0.0, 1.5, 1000.8];
+    xtb.update_external_charges(&positions)?;
+    let energy_far = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy_far, energy_gas, epsilon = 1e-4);
+
+    // invalid point charges are rejected and the previous ones are kept
+    assert!(xtb.update_external_charges(&positions[..3]).is_err());
+    assert!(xtb.set_external_charges(&numbers, &charges[..1], &positions).is_err());
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy_far, epsilon = 1e-9);
+
+    xtb.release_external_charges()?;
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy_gas, epsilon = 1e-9);
+
+    // not available for GFN-FF
+    let mut params = XtbParameters::default();
+    params.method("GFN-FF");
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    assert!(xtb.set_external_charges(&numbers, &charges, &positions).is_err());
+
+    Ok(())
+}
+// c47a0e93 ends here
+
+// [[file:../xtb.note::2d7f95b1][2d7f95b1]]
+#[test]
+fn test_xtb_model_output() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let mut gradient = coord;
+
+    // capture output into string
+    let mut params = XtbParameters::default();
+    params.output_minimal().capture_output();
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    assert!(xtb.get_output().is_none());
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    let output = xtb.get_output().unwrap();
+    assert!(!output.is_empty());
+
+    // write output into file
+    let path = std::env::temp_dir().join("xtb-model-test-output.log");
+    let mut params = XtbParameters::default();
+    params.output_minimal().output_file(&path);
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert!(xtb.get_output().is_none());
+    drop(xtb);
+    let output = std::fs::read_to_string(&path)?;
+    assert!(!output.is_empty());
+    std::fs::remove_file(&path)?;
+
+    Ok(())
+}
+// 2d7f95b1 ends here
+
+// [[file:../xtb.note::71e0c5a8][71e0c5a8]]
+#[test]
+fn test_xtb_model_restart() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let mut gradient = coord;
+
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    // SCC restarted from the wavefunction of previous geometry
+    let mut coord_new = coord;
+    coord_new[2] -= 0.05;
+    xtb.update_structure(&coord_new, None)?;
+    let energy_restart = xtb.calculate_energy_and_gradient(&mut gradient)?;
+
+    // SCC started from scratch
+    xtb.reset_guess();
+    let energy_scratch = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy_restart, energy_scratch, epsilon = 1e-6);
+
+    let mut xtb = XtbModel::create(&attyp, &coord_new, None)?;
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy_scratch, epsilon = 1e-6);
+
+    Ok(())
+}
+// 71e0c5a8 ends here
+
+// [[file:../xtb.note::3f6a9b27][3f6a9b27]]
+#[test]
+fn test_xtb_model_set_parameters() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let mut gradient = coord;
+
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, -8.3824793849585, epsilon = 1e-9);
+    // no parametrization reloading
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, -8.3824793849585, epsilon = 1e-9);
+
+    // switch method
+    let mut params = xtb.parameters().clone();
+    params.method("GFN1-xTB");
+    xtb.set_parameters(params.clone())?;
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, -8.424757953815186, epsilon = 1e-9);
+
+    // switch charge
+    params.charge(1.0).unpaired_electrons(1);
+    xtb.set_parameters(params.clone())?;
+    let energy_cation = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    let mut xtb_cation = XtbModel::create(&attyp, &coord, params)?;
+    let energy = xtb_cation.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy_cation, epsilon = 1e-6);
+
+    Ok(())
+}
+// 3f6a9b27 ends here
+
+// [[file:../xtb.note::6b1e94d2][6b1e94d2]]
+#[test]
+fn test_xtb_model_no_reload() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let mut gradient = coord;
+
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    assert_eq!(xtb.parametrization_loads(), 1);
+    for _ in 0..3 {
+        xtb.calculate_energy_and_gradient(&mut gradient)?;
+    }
+    assert_eq!(xtb.parametrization_loads(), 1);
+
+    // SCC settings are applied without reloading parametrization
+    let mut params = xtb.parameters().clone();
+    params.electronic_temperature(500.0).max_iterations(500);
+    xtb.set_parameters(params.clone())?;
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_eq!(xtb.parametrization_loads(), 1);
+
+    // but a different method has to be loaded
+    params.method("GFN1-xTB");
+    xtb.set_parameters(params)?;
+    xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_eq!(xtb.parametrization_loads(), 2);
+
+    Ok(())
+}
+// 6b1e94d2 ends here
+
+// [[file:../xtb.note::5a8c2e41][5a8c2e41]]
+#[test]
+fn test_xtb_model_output_properties() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let natoms = attyp.len();
+
+    let mut xtb = XtbModel::create(&attyp, &coord, None)?;
+    let output = xtb.calculate()?;
+    assert_relative_eq!(output.energy, -8.3824793849585, epsilon = 1e-9);
+    assert_relative_eq!(output.dipole[2], -0.298279305689518, epsilon = 1e-6);
+    assert_eq!(output.gradient.len(), natoms * 3);
+    assert_eq!(output.charges.unwrap().len(), natoms);
+    assert!(output.virial.iter().all(|x| x.is_finite()));
+    assert!(output.bond_orders.is_none());
+    assert!(output.orbital_coefficients.is_none());
+
+    let mut params = XtbParameters::default();
+    params.bond_orders(true).orbital_energies(true).orbital_coefficients(true);
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    let output = xtb.calculate()?;
+    let nao = output.nao.unwrap();
+    assert!(nao > 0);
+    let wbo = output.bond_orders.unwrap();
+    assert_eq!(wbo.len(), natoms * natoms);
+    // C-C triple bond in propyne
+    assert!(wbo[natoms + 2] > 2.5);
+    assert_eq!(output.orbital_eigenvalues.unwrap().len(), nao);
+    let focc = output.orbital_occupations.unwrap();
+    assert_relative_eq!(focc.iter().sum::<f64>(), 16.0, epsilon = 1e-6);
+    assert_eq!(output.orbital_coefficients.unwrap().len(), nao * nao);
+
+    // no partial charges from GFN-FF
+    let mut params = XtbParameters::default();
+    params.method("GFN-FF");
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    let output = xtb.calculate()?;
+    assert!(output.charges.is_none());
+
+    Ok(())
+}
+// 5a8c2e41 ends here
+
+// [[file:../xtb.note::b6e27f10][b6e27f10]]
+#[test]
+fn test_xtb_model_snapshot() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let mut gradient = coord;
+
+    let mut params = XtbParameters::default();
+    params.keep_history(2);
+    let mut xtb = XtbModel::create(&attyp, &coord, params)?;
+    assert!(xtb.snapshot().is_none());
+    let energy0 = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    let snapshot = xtb.snapshot().unwrap();
+    assert_relative_eq!(snapshot.energy(), energy0, epsilon = 1e-9);
+
+    // take a few steps
+    let mut coord_new = coord;
+    for _ in 0..3 {
+        coord_new[2] -= 0.05;
+        xtb.update_structure(&coord_new, None)?;
+        xtb.calculate_energy_and_gradient(&mut gradient)?;
+    }
+    // bounded history
+    let history: Vec<_> = xtb.history().collect();
+    assert_eq!(history.len(), 2);
+    assert_relative_eq!(history[1].coord()[2], coord_new[2], epsilon = 1e-9);
+
+    // roll back
+    xtb.restore(&snapshot)?;
+    assert_eq!(xtb.get_dipole(), Some(snapshot.output().dipole));
+    let energy = xtb.calculate_energy_and_gradient(&mut gradient)?;
+    assert_relative_eq!(energy, energy0, epsilon = 1e-8);
+
+    xtb.clear_history();
+    assert_eq!(xtb.history().count(), 0);
+
+    Ok(())
+}
+// b6e27f10 ends here
+
+// [[file:../xtb.note::9d31c5e2][9d31c5e2]]
+#[test]
+fn test_xtb_model_parameter_file() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+
+    // missing parameter file is reported before calling xtb
+    let mut params = XtbParameters::default();
+    params.parameter_file("/nonexistent/param_gfn2-xtb.txt");
+    let err = XtbModel::create(&attyp, &coord, params).err().unwrap();
+    assert!(err.to_string().contains("not found"));
+
+    Ok(())
+}
+// 9d31c5e2 ends here
@@ -0,0 +1,50 @@
+// [[file:../xtb.note::7b2d9f40][7b2d9f40]]
+use anyhow::*;
+use xtb_model::neb::*;
+use xtb_model::opt::*;
+use xtb_model::ts::Prfo;
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_neb_hcn() -> Result<()> {
+    let atom_types = [6, 7, 1];
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let create = |x: &[f64]| XtbModel::create(&atom_types, x, params.clone());
+
+    // HCN and HNC
+    let hcn = [0.0, 0.0, 0.0, 0.0, 0.0, 2.2, 0.0, 0.1, -2.0];
+    let hnc = [0.0, 0.0, 0.0, 0.0, 0.0, 2.2, 0.0, 0.1, 4.1];
+    let mut xtb = create(&hcn)?;
+    let opt = Lbfgs::default();
+    let hcn = opt.minimize(&mut xtb, &hcn)?.positions;
+    let hnc = opt.minimize(&mut xtb, &hnc)?.positions;
+
+    let path = Neb::default().images(5).interpolate(&hcn, &hnc)?;
+    assert_eq!(path.len(), 7);
+
+    let report = Neb::default()
+        .images(5)
+        .optimizer(NebOptimizer::Lbfgs)
+        .run(create, &hcn, &hnc)?;
+    assert!(report.converged());
+    assert!(report.climbing_image.is_some());
+    assert_eq!(report.energies.len(), 7);
+    // HNC is less stable
+    assert!(report.reaction_energy() > 0.0);
+    assert!(report.barrier() > report.reaction_energy());
+
+    let path = std::env::temp_dir().join("xtb-model-test-neb.dat");
+    report.write_profile(&path)?;
+    let profile = std::fs::read_to_string(&path)?;
+    assert_eq!(profile.lines().filter(|l| !l.starts_with('#')).count(), 7);
+    std::fs::remove_file(&path)?;
+
+    // refine the climbing image
+    let ts = Prfo::new(&atom_types)?.search(&mut xtb, report.ts_guess())?;
+    assert!(ts.is_transition_state());
+    assert!((ts.energy - report.energies[report.highest_image()]).abs() < 1e-3);
+
+    Ok(())
+}
+// 7b2d9f40 ends here
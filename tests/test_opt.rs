@@ -0,0 +1,72 @@
+// [[file:../xtb.note::2b8f6e03][2b8f6e03]]
+use anyhow::*;
+use xtb_model::constraints::{ConstrainedPotential, Constraints};
+use xtb_model::opt::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::XtbModel;
+
+#[test]
+fn test_opt_lbfgs() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &coord, None)?;
+    let mut gradient = coord;
+    let energy0 = xtb.calculate_energy_and_gradient(&mut gradient)?;
+
+    let mut nsteps = 0;
+    let report = Lbfgs::default().max_steps(100).minimize_with(&mut xtb, &coord, |p| {
+        nsteps = p.step;
+        true
+    })?;
+    assert!(report.converged());
+    assert!(report.energy < energy0);
+    assert_eq!(report.n_steps, nsteps);
+    assert!(report.gradient.iter().all(|x| x.abs() < 1e-3));
+
+    // stop by callback
+    let report = Lbfgs::default().minimize_with(&mut xtb, &coord, |p| p.step < 2)?;
+    assert_eq!(report.termination, OptTermination::Stopped);
+    assert_eq!(report.n_steps, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_opt_fire() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &coord, None)?;
+    let report_lbfgs = Lbfgs::default().minimize(&mut xtb, &coord)?;
+
+    let report = Fire::default().max_steps(1000).minimize(&mut xtb, &coord)?;
+    assert!(report.converged());
+    assert!((report.energy - report_lbfgs.energy).abs() < 1e-4);
+
+    Ok(())
+}
+
+#[test]
+fn test_opt_internal() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &coord, None)?;
+    let report_lbfgs = Lbfgs::default().minimize(&mut xtb, &coord)?;
+
+    let report = InternalOpt::new(&ATOM_TYPES)?.minimize(&mut xtb, &coord)?;
+    assert!(report.converged());
+    assert!((report.energy - report_lbfgs.energy).abs() < 1e-4);
+    assert!(report.n_evaluations <= report_lbfgs.n_evaluations);
+
+    // frozen atoms stay in place
+    let mut constraints = Constraints::default();
+    constraints.freeze_atoms(&[0, 3]);
+    let mut pot = ConstrainedPotential::new(&mut xtb, constraints);
+    let report = InternalOpt::new(&ATOM_TYPES)?.minimize(&mut pot, &coord)?;
+    assert!(report.converged());
+    assert_eq!(report.positions[..3], coord[..3]);
+    assert_eq!(report.positions[9..12], coord[9..12]);
+
+    // atom types should match positions
+    assert!(InternalOpt::new(&ATOM_TYPES[1..])?.minimize(&mut xtb, &coord).is_err());
+    assert!(InternalOpt::new(&[0]).is_err());
+
+    Ok(())
+}
+// 2b8f6e03 ends here
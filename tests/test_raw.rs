@@ -27,3 +27,41 @@ fn test_xtb_raw_api() -> Result<()> {
     Ok(())
 }
 // 1c066e9f ends here
+
+// [[file:../xtb.note::0c9e4b7d][0c9e4b7d]]
+#[test]
+fn test_xtb_raw_results_getters() -> Result<()> {
+    let coord = ATOM_COORDS;
+    let attyp = [6, 6, 6, 1, 1, 1, 1];
+    let natoms = attyp.len();
+
+    let env = XtbEnvironment::new();
+    let mol = XtbMolecule::create(&env, &attyp, &coord, 0.0, 0, None, None)?;
+    let calc = XtbCalculator::new();
+    calc.load_parametrization(&mol, &env, XtbMethod::GFN2xTB)?;
+
+    // no results yet
+    let mut res = XtbResults::new();
+    assert!(res.natoms().is_none());
+    assert!(res.gradient(&env).is_err());
+
+    calc.single_point_into(&mol, &env, &mut res)?;
+    assert_eq!(res.natoms(), Some(natoms));
+    // undersized buffer is rejected
+    let mut gradient = vec![0.0; natoms];
+    assert!(res.get_gradient(&env, &mut gradient).is_err());
+    let mut gradient = vec![0.0; natoms * 3];
+    res.get_gradient(&env, &mut gradient)?;
+    assert_eq!(res.gradient(&env)?, gradient);
+
+    assert_eq!(res.charges(&env)?.len(), natoms);
+    assert_eq!(res.bond_orders(&env)?.len(), natoms * natoms);
+    assert_eq!(res.virial(&env)?.len(), 9);
+    let nao = res.get_nao(&env)?;
+    assert_eq!(res.orbital_eigenvalues(&env)?.len(), nao);
+    assert_eq!(res.orbital_occupations(&env)?.len(), nao);
+    assert_eq!(res.orbital_coefficients(&env)?.len(), nao * nao);
+
+    Ok(())
+}
+// 0c9e4b7d ends here
@@ -0,0 +1,46 @@
+// [[file:../xtb.note::7c3e9a52][7c3e9a52]]
+use anyhow::*;
+use xtb_model::internal::InternalCoordinate;
+use xtb_model::opt::Lbfgs;
+use xtb_model::scan::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::XtbModel;
+
+#[test]
+fn test_scan_distance() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    let coord = Lbfgs::default().minimize(&mut xtb, &ATOM_COORDS)?.positions;
+    let bond = InternalCoordinate::Bond(0, 1);
+    let r0 = bond.value(&coord);
+
+    let report = Scan::new(&ATOM_TYPES)
+        .distance(0, 1, r0 - 0.2, r0 + 0.2, 5)
+        .run(&mut xtb, &coord)?;
+    assert!(report.converged());
+    assert_eq!(report.points.len(), 5);
+    for p in report.points.iter() {
+        assert!((bond.value(&p.positions) - p.values[0]).abs() < 0.05);
+    }
+    // the relaxed minimum is in the middle
+    assert_eq!(report.lowest().indices, [2]);
+
+    let path = std::env::temp_dir().join("test_scan_distance.dat");
+    report.write_table(&path)?;
+    let n_lines = std::fs::read_to_string(&path)?.lines().count();
+    assert_eq!(n_lines, 6);
+
+    // two coordinates with early stop
+    let mut n = 0;
+    let report = Scan::new(&ATOM_TYPES)
+        .distance(0, 1, r0 - 0.1, r0 + 0.1, 2)
+        .angle(0, 1, 2, 170.0, 175.0, 2)
+        .run_with(&mut xtb, &coord, |_| {
+            n += 1;
+            n < 3
+        })?;
+    assert_eq!(report.points.len(), 3);
+    assert_eq!(report.points[2].indices, [1, 1]);
+
+    Ok(())
+}
+// 7c3e9a52 ends here
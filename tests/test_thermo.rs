@@ -0,0 +1,64 @@
+// [[file:../xtb.note::6b13e0f4][6b13e0f4]]
+use anyhow::*;
+use approx::assert_relative_eq;
+use xtb_model::hessian::*;
+use xtb_model::opt::*;
+use xtb_model::test::{ATOM_COORDS, ATOM_TYPES};
+use xtb_model::thermo::*;
+use xtb_model::XtbModel;
+
+#[test]
+fn test_thermo_rrho() -> Result<()> {
+    let mut xtb = XtbModel::create(&ATOM_TYPES, &ATOM_COORDS, None)?;
+    Lbfgs::default()
+        .convergence(OptConvergence::tight())
+        .minimize(&mut xtb, &ATOM_COORDS)?;
+    let coord = xtb.coord().to_vec();
+    let hessian = NumericalHessian::default().compute(&mut xtb, &coord)?;
+    let vib = VibrationalAnalysis::new(xtb.atom_types())?;
+    let modes = vib.normal_modes(&coord, &hessian)?;
+
+    let report = Thermochemistry::default().compute(vib.masses(), &coord, &modes)?;
+    assert!(!report.linear);
+    assert_eq!(report.n_imaginary, 0);
+    // ZPE of propyne is about 35 kcal/mol
+    assert!(report.zpe > 0.05 && report.zpe < 0.06, "{}", report.zpe);
+    assert!(report.enthalpy > report.zpe);
+    assert_relative_eq!(report.gibbs, report.enthalpy - 298.15 * report.entropy, epsilon = 1e-12);
+    assert_relative_eq!(report.total_free_energy(-1.0), report.gibbs - 1.0);
+
+    // C3v symmetry lowers rotational entropy by k ln(3)
+    let report_c3v = Thermochemistry::default()
+        .symmetry_number(3)
+        .compute(vib.masses(), &coord, &modes)?;
+    let kb = 3.166811563e-6;
+    assert_relative_eq!(report.entropy - report_c3v.entropy, kb * 3f64.ln(), epsilon = 1e-9);
+
+    // higher temperature increases entropy
+    let report_hot = Thermochemistry::default()
+        .temperature(500.0)
+        .compute(vib.masses(), &coord, &modes)?;
+    assert!(report_hot.entropy > report.entropy);
+
+    // rigid rotor and ideal gas contributions: H(T) - ZPE = 4kT plus a small
+    // thermal population of the bending modes
+    let kt = kb * 298.15;
+    assert_relative_eq!(report.translational.enthalpy, 2.5 * kt, epsilon = 1e-10);
+    assert_relative_eq!(report.rotational.enthalpy, 1.5 * kt, epsilon = 1e-10);
+    let h_vib = (report.enthalpy - report.zpe - 4.0 * kt) * 627.5095;
+    assert!(h_vib > 0.3 && h_vib < 1.5, "{}", h_vib);
+    // standard entropy of propyne is 248.1 J/(mol K) (NIST)
+    let s = report_c3v.entropy * 2625499.6;
+    assert_relative_eq!(s, 248.1, max_relative = 0.02);
+    assert_relative_eq!(report_c3v.gibbs, report_c3v.enthalpy - 298.15 * report_c3v.entropy, epsilon = 1e-12);
+
+    // a slightly bent triatomic is treated as linear in both places
+    let x = [0.0, 0.0, 0.0, 2.2, 0.0, 0.0, 4.4, 0.03, 0.0];
+    assert!(is_linear(&[12.0, 16.0, 12.0], &x));
+    let modes = VibrationalAnalysis::new(&[6, 8, 6])?.normal_modes(&x, &[0.0; 81])?;
+    assert!(modes.linear);
+    assert_eq!(modes.frequencies.len(), 4);
+
+    Ok(())
+}
+// 6b13e0f4 ends here
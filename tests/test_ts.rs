@@ -0,0 +1,59 @@
+// [[file:../xtb.note::3c96e0d5][3c96e0d5]]
+use anyhow::*;
+use xtb_model::opt::{Lbfgs, OptConvergence};
+use xtb_model::ts::*;
+use xtb_model::{XtbModel, XtbParameters};
+
+#[test]
+fn test_ts_hcn() -> Result<()> {
+    // guess of transition state of HCN <-> HNC isomerization
+    let atom_types = [6, 7, 1];
+    let coord = [0.0, 0.0, 0.0, 0.0, 0.0, 2.25, 2.0, 0.0, 1.0];
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&atom_types, &coord, params)?;
+
+    let report = Prfo::new(&atom_types)?
+        .convergence(OptConvergence::tight())
+        .search(&mut xtb, &coord)?;
+    assert!(report.is_transition_state());
+    let freq = report.imaginary_frequency().unwrap();
+    assert!(freq < -300.0, "{freq}");
+
+    // hydrogen migrating along C-N bond
+    let report_dimer = Dimer::new(&atom_types)?
+        .convergence(OptConvergence::tight())
+        .direction(&[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0])
+        .search(&mut xtb, &coord)?;
+    assert!(report_dimer.is_transition_state());
+    assert!((report_dimer.energy - report.energy).abs() < 1e-5);
+
+    // without verification
+    let report = Prfo::new(&atom_types)?.verify(false).max_steps(2).search(&mut xtb, &coord)?;
+    assert!(report.modes.is_none());
+    assert!(!report.is_transition_state());
+
+    Ok(())
+}
+
+#[test]
+fn test_ts_from_minimum() -> Result<()> {
+    // a stationary point with positive curvature is not a transition state
+    let atom_types = [6, 7, 1];
+    let coord = [0.0, 0.0, 0.0, 0.0, 0.0, 2.2, 0.0, 0.0, -2.0];
+    let mut params = XtbParameters::default();
+    params.output_muted();
+    let mut xtb = XtbModel::create(&atom_types, &coord, params)?;
+    let coord = Lbfgs::default()
+        .convergence(OptConvergence::tight())
+        .minimize(&mut xtb, &coord)?
+        .positions;
+
+    let report = Prfo::new(&atom_types)?.max_steps(3).search(&mut xtb, &coord)?;
+    assert!(!report.converged());
+    let report = Dimer::new(&atom_types)?.max_steps(3).search(&mut xtb, &coord)?;
+    assert!(!report.converged());
+
+    Ok(())
+}
+// 3c96e0d5 ends here